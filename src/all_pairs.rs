@@ -0,0 +1,186 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time;
+
+use crate::grid::Grid;
+
+
+// Above this many open squares Floyd–Warshall, which takes their number
+// cubed steps, is slower than a Dijkstra search from every square
+const FLOYD_WARSHALL_LIMIT: usize = 400;
+
+
+// Above this many open squares the table takes seconds to fill
+pub const ALL_PAIRS_LIMIT: usize = 2500;
+
+
+// Marks a square that can't be reached in the table
+const UNREACHABLE: usize = usize::MAX;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Method {
+    FloydWarshall,
+    RepeatedDijkstra
+}
+
+
+impl Method {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::FloydWarshall => "Floyd–Warshall",
+            Method::RepeatedDijkstra => "Dijkstra from every square"
+        }
+    }
+
+
+    // The faster one for a grid with this many open squares
+    pub fn for_size(squares: usize) -> Method {
+        if squares <= FLOYD_WARSHALL_LIMIT { Method::FloydWarshall } else { Method::RepeatedDijkstra }
+    }
+}
+
+
+// Path cost between every two open squares
+pub struct DistanceTable {
+    pub method: Method,
+    // Open squares, sorted, in the order of the rows and columns
+    pub squares: Vec<(usize, usize)>,
+    index: HashMap<(usize, usize), usize>,
+    // Row of the square the paths start at, column of the one they end at
+    distances: Vec<usize>,
+    pub duration: time::Duration,
+}
+
+
+impl DistanceTable {
+    // None when either square is a wall or the second can't be reached from the first
+    pub fn distance(&self, from: (usize, usize), to: (usize, usize)) -> Option<usize> {
+        let (i, j) = (*self.index.get(&from)?, *self.index.get(&to)?);
+        match self.distances[i * self.squares.len() + j] {
+            UNREACHABLE => None,
+            distance => Some(distance)
+        }
+    }
+
+
+    // Every square reachable from the given one and its distance
+    pub fn distances_from(&self, from: (usize, usize)) -> Vec<((usize, usize), usize)> {
+        self.squares
+            .iter()
+            .filter_map(|to| self.distance(from, *to).map(|distance| (*to, distance)))
+            .collect()
+    }
+
+
+    // Longest distance between two squares that can reach each other
+    pub fn diameter(&self) -> usize {
+        self.distances.iter().filter(|distance| **distance != UNREACHABLE).max().copied().unwrap_or(0)
+    }
+
+
+    // Over every ordered pair of different squares that can reach each other
+    pub fn average_distance(&self) -> f64 {
+        let reachable: Vec<usize> = self.distances.iter().copied().filter(|distance| *distance != UNREACHABLE && *distance > 0).collect();
+        if reachable.is_empty() { return 0.0 }
+        reachable.iter().map(|distance| *distance as f64).sum::<f64>() / reachable.len() as f64
+    }
+}
+
+
+// Distances between every two open squares, with the moves and costs of the
+// searches. None when there are more than ALL_PAIRS_LIMIT open squares.
+pub fn compute(grid: &Grid, method: Method) -> Option<DistanceTable> {
+    let time_start = time::Instant::now();
+    let squares: Vec<(usize, usize)> = (0..grid.width())
+        .flat_map(|x| (0..grid.height()).map(move |y| (x, y)))
+        .filter(|sq| grid.is_walkable(*sq))
+        .collect();
+    if squares.len() > ALL_PAIRS_LIMIT { return None }
+    let index: HashMap<(usize, usize), usize> = squares.iter().enumerate().map(|(i, sq)| (*sq, i)).collect();
+    let distances = match method {
+        Method::FloydWarshall => floyd_warshall(grid, &squares, &index),
+        Method::RepeatedDijkstra => repeated_dijkstra(grid, &squares, &index)
+    };
+    Some(DistanceTable { method, squares, index, distances, duration: time_start.elapsed() })
+}
+
+
+// Every square in turn is allowed as a stop on the way between every two
+// https://en.wikipedia.org/wiki/Floyd%E2%80%93Warshall_algorithm
+fn floyd_warshall(grid: &Grid, squares: &[(usize, usize)], index: &HashMap<(usize, usize), usize>) -> Vec<usize> {
+    let n = squares.len();
+    let mut distances = vec![UNREACHABLE; n * n];
+    for (i, sq) in squares.iter().enumerate() {
+        distances[i * n + i] = 0;
+        for next in grid.neighbors(*sq) {
+            let j = index[&next];
+            distances[i * n + j] = distances[i * n + j].min(grid.step_cost(*sq, next));
+        }
+    }
+    for k in 0..n {
+        for i in 0..n {
+            let through = distances[i * n + k];
+            if through == UNREACHABLE { continue }
+            for j in 0..n {
+                let rest = distances[k * n + j];
+                if rest != UNREACHABLE && through + rest < distances[i * n + j] {
+                    distances[i * n + j] = through + rest;
+                }
+            }
+        }
+    }
+    distances
+}
+
+
+fn repeated_dijkstra(grid: &Grid, squares: &[(usize, usize)], index: &HashMap<(usize, usize), usize>) -> Vec<usize> {
+    let n = squares.len();
+    let mut distances = vec![UNREACHABLE; n * n];
+    for (i, source) in squares.iter().enumerate() {
+        let row = &mut distances[i * n..(i + 1) * n];
+        row[i] = 0;
+        let mut open_set = BinaryHeap::from([Reverse((0, *source))]);
+        while let Some(Reverse((cost, current))) = open_set.pop() {
+            if cost > row[index[&current]] { continue }
+            for next in grid.neighbors(current) {
+                let next_cost = cost + grid.step_cost(current, next);
+                let j = index[&next];
+                if next_cost < row[j] {
+                    row[j] = next_cost;
+                    open_set.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+    }
+    distances
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_file;
+    use crate::search::{self, Algorithm};
+
+
+    #[test]
+    fn both_methods_match_dijkstra_searches() {
+        let mut grid = map_file::parse_map("a..#...\n.5>#.9.\n.#.v.#.\n...<..a\n##.#.>.\n").unwrap();
+        grid.set_portal_cost(3);
+        assert_eq!(Method::for_size(grid.width() * grid.height()), Method::FloydWarshall);
+        let floyd_warshall = compute(&grid, Method::FloydWarshall).unwrap();
+        let repeated_dijkstra = compute(&grid, Method::RepeatedDijkstra).unwrap();
+        assert_eq!(floyd_warshall.squares, repeated_dijkstra.squares);
+        for from in &floyd_warshall.squares {
+            for to in &floyd_warshall.squares {
+                let searched = search::search(&grid, *from, *to, Algorithm::Dijkstra);
+                let expected = if searched.found() { Some(searched.cost) } else { None };
+                assert_eq!(floyd_warshall.distance(*from, *to), expected, "Floyd–Warshall from {from:?} to {to:?}");
+                assert_eq!(repeated_dijkstra.distance(*from, *to), expected, "repeated Dijkstra from {from:?} to {to:?}");
+            }
+        }
+        // The one-way squares leave some pairs unreachable one way only
+        assert!(floyd_warshall.squares.iter().any(|sq| floyd_warshall.distances_from(*sq).len() < floyd_warshall.squares.len()));
+    }
+}
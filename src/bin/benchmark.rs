@@ -0,0 +1,277 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use pathfinding::generators::{self, Generator, GeneratorOptions};
+use pathfinding::grid::{Grid, Topology};
+use pathfinding::hierarchical::Hierarchy;
+use pathfinding::map_file;
+use pathfinding::search::{self, Algorithm};
+
+
+const USAGE: &str = "Usage: benchmark (--maps <dir> | --random <count>) [--generator NAME] [--seed N] [--density D] [--iterations N] [--braid F] [--width W] [--height H] [--topology square|pointy|flat] [--wrap true|false] [--portal-cost N] [--clusters N] [--queries N] [--csv <file>]";
+
+
+struct Options {
+    maps_dir: Option<String>,
+    random_maps: usize,
+    generator: Generator,
+    seed: u64,
+    generator_options: GeneratorOptions,
+    width: usize,
+    height: usize,
+    topology: Topology,
+    wrap: bool,
+    portal_cost: usize,
+    // HPA* runs too when above 0, with clusters this many squares wide
+    clusters: usize,
+    queries: usize,
+    csv_path: String,
+}
+
+
+// One algorithm solving one start/goal query on one map
+struct Run {
+    map: String,
+    query: usize,
+    start: (usize, usize),
+    goal: (usize, usize),
+    // Short and full name of the algorithm
+    algorithm: (&'static str, &'static str),
+    found: bool,
+    nodes_expanded: usize,
+    cost: usize,
+    // Extra cost over Dijkstra's optimal path, as a fraction of it
+    optimality_gap: Option<f64>,
+    time_us: u128,
+}
+
+
+// Runs every algorithm over many maps and writes the numbers to a CSV file
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => exit_with_error(&e)
+    };
+    let maps = match &options.maps_dir {
+        Some(dir) => read_maps(dir),
+        None => Ok(random_maps(&options)),
+    };
+    let mut maps = match maps {
+        Ok(maps) => maps,
+        Err(e) => exit_with_error(&e)
+    };
+    for (_, grid) in &mut maps {
+        grid.set_topology(options.topology);
+        grid.set_wrap(options.wrap);
+        grid.set_portal_cost(options.portal_cost);
+    }
+
+    // Queries are seeded too, so the same options repeat the same runs
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut runs: Vec<Run> = Vec::new();
+    let mut build_times_us: Vec<u128> = Vec::new();
+    for (name, grid) in &maps {
+        let hierarchy = if options.clusters > 0 { Some(Hierarchy::build(grid, options.clusters)) } else { None };
+        build_times_us.extend(hierarchy.iter().map(|hierarchy| hierarchy.duration.as_micros()));
+        let walkable: Vec<(usize, usize)> = (0..grid.width())
+            .flat_map(|x| (0..grid.height()).map(move |y| (x, y)))
+            .filter(|sq| grid.is_walkable(*sq))
+            .collect();
+        if walkable.len() < 2 { continue }
+        for query in 0..options.queries {
+            let pair: Vec<&(usize, usize)> = walkable.choose_multiple(&mut rng, 2).collect();
+            runs.extend(run_query(name, query, grid, hierarchy.as_ref(), *pair[0], *pair[1]));
+        }
+    }
+
+    if let Err(e) = fs::write(&options.csv_path, format_csv(&runs)) {
+        exit_with_error(&format!("could not write {}: {e}", options.csv_path));
+    }
+    println!("{} maps, {} queries each, seed {}, results written to {}", maps.len(), options.queries, options.seed, options.csv_path);
+    print!("{}", format_summary(&runs));
+    if !build_times_us.is_empty() {
+        let average = build_times_us.iter().sum::<u128>() as f64 / build_times_us.len() as f64;
+        println!("HPA* graphs built in {average:.1} us per map on average, not counted in the query times");
+    }
+}
+
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        maps_dir: None,
+        random_maps: 0,
+        generator: Generator::RandomFill,
+        seed: generators::random_seed(),
+        generator_options: GeneratorOptions::default(),
+        width: 32,
+        height: 18,
+        topology: Topology::Square,
+        wrap: false,
+        portal_cost: 1,
+        clusters: 0,
+        queries: 10,
+        csv_path: "benchmark.csv".to_string(),
+    };
+    let parse_number = |arg: &str, value: &str| value.parse::<usize>().map_err(|_| format!("invalid value '{value}' for {arg}"));
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "--maps" => options.maps_dir = Some(value),
+            "--random" => options.random_maps = parse_number(&arg, &value)?,
+            "--generator" => {
+                options.generator = Generator::from_id(&value).ok_or(format!("unknown generator '{value}'"))?;
+            },
+            "--seed" => options.seed = value.parse::<u64>().map_err(|_| format!("invalid value '{value}' for {arg}"))?,
+            "--density" => {
+                options.generator_options.density = value.parse::<f64>().map_err(|_| format!("invalid value '{value}' for {arg}"))?;
+            },
+            "--iterations" => options.generator_options.iterations = parse_number(&arg, &value)?,
+            "--braid" => {
+                options.generator_options.braid = value.parse::<f64>().map_err(|_| format!("invalid value '{value}' for {arg}"))?;
+            },
+            "--width" => options.width = parse_number(&arg, &value)?,
+            "--height" => options.height = parse_number(&arg, &value)?,
+            "--topology" => {
+                options.topology = Topology::from_id(&value).ok_or(format!("unknown topology '{value}'"))?;
+            },
+            "--portal-cost" => options.portal_cost = parse_number(&arg, &value)?,
+            "--wrap" => options.wrap = value.parse::<bool>().map_err(|_| format!("invalid value '{value}' for {arg}"))?,
+            "--clusters" => options.clusters = parse_number(&arg, &value)?,
+            "--queries" => options.queries = parse_number(&arg, &value)?,
+            "--csv" => options.csv_path = value,
+            _ => return Err(format!("unknown option {arg}"))
+        }
+    }
+    if options.maps_dir.is_some() == (options.random_maps > 0) {
+        return Err("expected either --maps or --random".to_string());
+    }
+    if options.width == 0 || options.height == 0 {
+        return Err("the random maps need at least one square".to_string());
+    }
+    Ok(options)
+}
+
+
+fn read_maps(dir: &str) -> Result<Vec<(String, Grid)>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("could not read {dir}: {e}"))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .collect();
+    paths.sort();
+    let mut maps: Vec<(String, Grid)> = Vec::new();
+    for path in paths {
+        let grid = map_file::read_map(&path).map_err(|e| format!("{path}: {e}"))?;
+        maps.push((path, grid));
+    }
+    Ok(maps)
+}
+
+
+// Map i uses the seed plus i, named after its generator and seed
+fn random_maps(options: &Options) -> Vec<(String, Grid)> {
+    let mut maps: Vec<(String, Grid)> = Vec::new();
+    for i in 0..options.random_maps as u64 {
+        let seed = options.seed.wrapping_add(i);
+        let generation = generators::generate(options.generator, options.width, options.height, seed, &options.generator_options);
+        maps.push((format!("{}-{seed}", options.generator.id()), generation.grid));
+    }
+    maps
+}
+
+
+fn run_query(map: &str, query: usize, grid: &Grid, hierarchy: Option<&Hierarchy>, start: (usize, usize), goal: (usize, usize)) -> Vec<Run> {
+    let results: Vec<search::SearchResult> = Algorithm::ALL
+        .iter()
+        .map(|algorithm| search::search(grid, start, goal, *algorithm))
+        .collect();
+    // None when Dijkstra found no path, so there is nothing to compare against
+    let optimal = results.iter().find(|result| result.algorithm == Algorithm::Dijkstra && result.found()).map(|result| result.cost);
+    let run = |algorithm: (&'static str, &'static str), found: bool, nodes_expanded: usize, cost: usize, time_us: u128| Run {
+        map: map.to_string(),
+        query,
+        start,
+        goal,
+        algorithm,
+        found,
+        nodes_expanded,
+        cost,
+        optimality_gap: match optimal {
+            Some(optimal) if found && optimal > 0 => Some((cost as f64 - optimal as f64) / optimal as f64),
+            Some(_) if found => Some(0.0),
+            _ => None
+        },
+        time_us,
+    };
+    let mut runs: Vec<Run> = results
+        .iter()
+        .map(|result| run((result.algorithm.id(), result.algorithm.name()), result.found(), result.nodes_expanded(), result.cost, result.duration.as_micros()))
+        .collect();
+    if let Some(hierarchy) = hierarchy {
+        let result = hierarchy.search(grid, start, goal);
+        runs.push(run(("hpa", "HPA*"), result.found(), result.nodes_expanded(), result.cost, result.duration.as_micros()));
+    }
+    runs
+}
+
+
+fn format_csv(runs: &[Run]) -> String {
+    let mut csv = String::from("map,query,start_x,start_y,goal_x,goal_y,algorithm,found,nodes_expanded,path_cost,optimality_gap,time_us\n");
+    for run in runs {
+        let cost = if run.found { run.cost.to_string() } else { String::new() };
+        let gap = run.optimality_gap.map_or(String::new(), |gap| format!("{gap:.4}"));
+        csv += &format!(
+            "{},{},{},{},{},{},{},{},{},{cost},{gap},{}\n",
+            run.map, run.query, run.start.0, run.start.1, run.goal.0, run.goal.1,
+            run.algorithm.0, run.found, run.nodes_expanded, run.time_us
+        );
+    }
+    csv
+}
+
+
+fn format_summary(runs: &[Run]) -> String {
+    let mut table = format!(
+        "{:<18} {:>6} {:>7} {:>13} {:>9} {:>9} {:>9} {:>14}\n",
+        "algorithm", "runs", "solved", "avg expanded", "avg cost", "avg gap", "max gap", "avg time (us)"
+    );
+    let mut algorithms: Vec<(&'static str, &'static str)> = Vec::new();
+    for run in runs {
+        if !algorithms.contains(&run.algorithm) {
+            algorithms.push(run.algorithm);
+        }
+    }
+    for algorithm in algorithms {
+        let algorithm_runs: Vec<&Run> = runs.iter().filter(|run| run.algorithm == algorithm).collect();
+        let solved: Vec<&&Run> = algorithm_runs.iter().filter(|run| run.found).collect();
+        let gaps: Vec<f64> = solved.iter().filter_map(|run| run.optimality_gap).collect();
+        let average = |total: f64, count: usize| if count == 0 { 0.0 } else { total / count as f64 };
+        table += &format!(
+            "{:<18} {:>6} {:>7} {:>13.1} {:>9.2} {:>8.2}% {:>8.2}% {:>14.1}\n",
+            algorithm.1,
+            algorithm_runs.len(),
+            solved.len(),
+            average(algorithm_runs.iter().map(|run| run.nodes_expanded as f64).sum(), algorithm_runs.len()),
+            average(solved.iter().map(|run| run.cost as f64).sum(), solved.len()),
+            average(gaps.iter().sum(), gaps.len()) * 100.0,
+            gaps.iter().cloned().fold(0.0, f64::max) * 100.0,
+            average(algorithm_runs.iter().map(|run| run.time_us as f64).sum(), algorithm_runs.len()),
+        );
+    }
+    table
+}
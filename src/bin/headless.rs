@@ -0,0 +1,171 @@
+use std::env;
+use std::process;
+
+use pathfinding::grid::{Grid, Topology};
+use pathfinding::map_file;
+use pathfinding::search::{self, Algorithm, Outcome, SearchResult};
+
+
+const USAGE: &str = "Usage: headless <map file> [--start X,Y] [--goal X,Y] [--algorithm astar|dijkstra|greedy] [--topology square|pointy|flat] [--wrap true|false] [--portal-cost N] [--format text|json]";
+
+
+struct Options {
+    map_path: String,
+    // Missing ones are reported in the result
+    start: Option<(usize, usize)>,
+    goal: Option<(usize, usize)>,
+    algorithm: Algorithm,
+    topology: Topology,
+    wrap: bool,
+    portal_cost: usize,
+    json: bool,
+}
+
+
+// Runs one search on a map file and prints the result, no window needed
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => exit_with_error(&e)
+    };
+    let mut grid = match map_file::read_map(&options.map_path) {
+        Ok(grid) => grid,
+        Err(e) => exit_with_error(&e)
+    };
+    grid.set_topology(options.topology);
+    grid.set_wrap(options.wrap);
+    grid.set_portal_cost(options.portal_cost);
+    for (pos, name) in [(options.start, "start"), (options.goal, "goal")] {
+        if let Some(Err(e)) = pos.map(|pos| check_square(&grid, pos, name)) {
+            eprintln!("warning: {e}");
+        }
+    }
+
+    let (outcome, result) = match search::check_endpoints(&grid, options.start, options.goal) {
+        Ok((start, goal)) => {
+            let result = search::search(&grid, start, goal, options.algorithm);
+            (result.outcome(), Some(result))
+        },
+        Err(outcome) => (outcome, None)
+    };
+    if options.json {
+        println!("{}", format_json(options.algorithm, &outcome, result.as_ref()));
+    } else {
+        print!("{}", format_text(options.algorithm, &outcome, result.as_ref()));
+    }
+}
+
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut map_path: Option<String> = None;
+    let mut start: Option<(usize, usize)> = None;
+    let mut goal: Option<(usize, usize)> = None;
+    let mut algorithm = Algorithm::AStar;
+    let mut topology = Topology::Square;
+    let mut wrap = false;
+    let mut portal_cost = 1;
+    let mut json = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--start" | "--goal" | "--algorithm" | "--topology" | "--wrap" | "--portal-cost" | "--format" => {
+                let value = args.next().ok_or(format!("missing value for {arg}"))?;
+                match arg.as_str() {
+                    "--start" => start = Some(parse_coordinates(&value)?),
+                    "--goal" => goal = Some(parse_coordinates(&value)?),
+                    "--algorithm" => {
+                        algorithm = Algorithm::from_id(&value).ok_or(format!("unknown algorithm '{value}'"))?;
+                    },
+                    "--topology" => {
+                        topology = Topology::from_id(&value).ok_or(format!("unknown topology '{value}'"))?;
+                    },
+                    "--wrap" => wrap = value.parse::<bool>().map_err(|_| format!("invalid value '{value}' for {arg}"))?,
+                    "--portal-cost" => portal_cost = value.parse::<usize>().map_err(|_| format!("invalid value '{value}' for {arg}"))?,
+                    _ => {
+                        json = match value.as_str() {
+                            "text" => false,
+                            "json" => true,
+                            _ => return Err(format!("unknown format '{value}'"))
+                        };
+                    }
+                }
+            },
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ if map_path.is_none() => map_path = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'"))
+        }
+    }
+
+    Ok(Options {
+        map_path: map_path.ok_or("missing map file")?,
+        start,
+        goal,
+        algorithm,
+        topology,
+        wrap,
+        portal_cost,
+        json,
+    })
+}
+
+
+fn parse_coordinates(value: &str) -> Result<(usize, usize), String> {
+    let error = || format!("invalid coordinates '{value}', expected X,Y");
+    let (x, y) = value.split_once(',').ok_or_else(error)?;
+    let x = x.trim().parse::<usize>().map_err(|_| error())?;
+    let y = y.trim().parse::<usize>().map_err(|_| error())?;
+    Ok((x, y))
+}
+
+
+fn check_square(grid: &Grid, pos: (usize, usize), name: &str) -> Result<(), String> {
+    if !grid.contains(pos) {
+        return Err(format!("{name} ({},{}) is outside the {}x{} map", pos.0, pos.1, grid.width(), grid.height()));
+    }
+    if !grid.is_walkable(pos) {
+        return Err(format!("{name} ({},{}) is a wall", pos.0, pos.1));
+    }
+    Ok(())
+}
+
+
+// The search numbers are left out when there was nothing to search
+fn format_text(algorithm: Algorithm, outcome: &Outcome, result: Option<&SearchResult>) -> String {
+    let mut text = String::new();
+    text += &format!("algorithm: {}\n", algorithm.name());
+    text += &format!("outcome: {}\n", outcome.describe());
+    if let Outcome::Found { path, cost } = outcome {
+        let path = path.iter().map(|sq| format!("({},{})", sq.0, sq.1)).collect::<Vec<String>>().join(" ");
+        text += &format!("path: {path}\n");
+        text += &format!("cost: {cost}\n");
+    }
+    if let Some(result) = result {
+        text += &format!("nodes expanded: {}\n", result.nodes_expanded());
+        text += &format!("time: {:.3} ms\n", result.duration.as_secs_f64() * 1000.0);
+    }
+    text
+}
+
+
+fn format_json(algorithm: Algorithm, outcome: &Outcome, result: Option<&SearchResult>) -> String {
+    let (path, cost) = match outcome {
+        Outcome::Found { path, cost } => (path.iter().map(|sq| format!("[{},{}]", sq.0, sq.1)).collect::<Vec<String>>().join(","), cost.to_string()),
+        _ => (String::new(), "null".to_string())
+    };
+    let nodes_expanded = result.map_or("null".to_string(), |result| result.nodes_expanded().to_string());
+    let time = result.map_or("null".to_string(), |result| result.duration.as_micros().to_string());
+    format!(
+        "{{\"algorithm\":\"{}\",\"outcome\":\"{}\",\"found\":{},\"path\":[{path}],\"cost\":{cost},\"nodes_expanded\":{nodes_expanded},\"time_us\":{time}}}",
+        algorithm.id(),
+        outcome.id(),
+        matches!(outcome, Outcome::Found { .. })
+    )
+}
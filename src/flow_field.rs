@@ -0,0 +1,115 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::grid::Grid;
+
+
+pub struct FlowField {
+    pub goal: (usize, usize),
+    // Cost of the cheapest way from every square that can reach the goal
+    pub costs: HashMap<(usize, usize), usize>,
+    // Neighbor to move to from every one of those squares but the goal
+    pub directions: HashMap<(usize, usize), (usize, usize)>,
+    pub duration: time::Duration,
+}
+
+
+impl FlowField {
+    // Squares from the given one to the goal, following the field.
+    // Only the square itself when it can't reach the goal.
+    pub fn path_from(&self, start: (usize, usize)) -> Vec<(usize, usize)> {
+        let mut path = vec![start];
+        let mut current = start;
+        while let Some(next) = self.directions.get(&current) {
+            path.push(*next);
+            current = *next;
+        }
+        path
+    }
+
+
+    // Random squares that can reach the goal, the same seed giving the same squares
+    pub fn spawn_units(&self, count: usize, seed: u64) -> Vec<(usize, usize)> {
+        let mut squares: Vec<(usize, usize)> = self.directions.keys().copied().collect();
+        squares.sort();
+        let rng = &mut StdRng::seed_from_u64(seed);
+        squares.choose_multiple(rng, count).copied().collect()
+    }
+}
+
+
+// Dijkstra from the goal over the moves turned around, which gives every
+// square its cost to the goal even with one-way squares and terrain. Every
+// square points at the neighbor it was reached from, the first step of its
+// cheapest way to the goal.
+// Emerson, "Crowd Pathfinding and Steering Using Flow Field Tiles", Game AI Pro (2013).
+pub fn build(grid: &Grid, goal: (usize, usize)) -> FlowField {
+    let time_start = time::Instant::now();
+    let mut incoming: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    for x in 0..grid.width() {
+        for y in 0..grid.height() {
+            if !grid.is_walkable((x, y)) { continue }
+            for next in grid.neighbors((x, y)) {
+                incoming.entry(next).or_default().push((x, y));
+            }
+        }
+    }
+
+    let mut costs: HashMap<(usize, usize), usize> = HashMap::from([(goal, 0)]);
+    let mut directions: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    let mut open_set = BinaryHeap::from([Reverse((0, goal))]);
+    while let Some(Reverse((cost, current))) = open_set.pop() {
+        if cost > costs[&current] { continue }
+        for previous in incoming.get(&current).into_iter().flatten() {
+            let previous_cost = cost + grid.step_cost(*previous, current);
+            if costs.get(previous).is_none_or(|old| previous_cost < *old) {
+                costs.insert(*previous, previous_cost);
+                directions.insert(*previous, current);
+                open_set.push(Reverse((previous_cost, *previous)));
+            }
+        }
+    }
+
+    FlowField { goal, costs, directions, duration: time_start.elapsed() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_file;
+    use crate::search::{self, Algorithm};
+
+
+    #[test]
+    fn following_the_field_matches_dijkstra() {
+        let mut grid = map_file::parse_map("a..#...\n.5>#.9.\n.#.v.#.\n...<..a\n##.#.>.\n").unwrap();
+        grid.set_portal_cost(3);
+        let squares: Vec<(usize, usize)> = (0..grid.width()).flat_map(|x| (0..grid.height()).map(move |y| (x, y))).filter(|sq| grid.is_walkable(*sq)).collect();
+        let mut some_unreachable = false;
+        for goal in &squares {
+            let field = build(&grid, *goal);
+            assert!(!field.directions.contains_key(goal));
+            for start in &squares {
+                let searched = search::search(&grid, *start, *goal, Algorithm::Dijkstra);
+                if !searched.found() {
+                    assert!(!field.directions.contains_key(start), "{start:?} points somewhere but can't reach {goal:?}");
+                    assert!(!field.costs.contains_key(start));
+                    some_unreachable = true;
+                    continue
+                }
+                let path = field.path_from(*start);
+                assert_eq!(path.last(), Some(goal));
+                let cost: usize = path.windows(2).map(|pair| grid.step_cost(pair[0], pair[1])).sum();
+                assert_eq!(cost, searched.cost, "from {start:?} to {goal:?}");
+                assert_eq!(field.costs[start], searched.cost);
+            }
+        }
+        assert!(some_unreachable);
+    }
+}
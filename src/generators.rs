@@ -0,0 +1,713 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::grid::{Cell, Grid, MAX_COST};
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Generator {
+    RandomFill,
+    RecursiveBacktracker,
+    Prim,
+    Kruskal,
+    Wilson,
+    Eller,
+    RecursiveDivision,
+    Cave,
+    Terrain
+}
+
+
+impl Generator {
+    pub const ALL: [Generator; 9] = [
+        Generator::RandomFill,
+        Generator::RecursiveBacktracker,
+        Generator::Prim,
+        Generator::Kruskal,
+        Generator::Wilson,
+        Generator::Eller,
+        Generator::RecursiveDivision,
+        Generator::Cave,
+        Generator::Terrain,
+    ];
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::RandomFill => "Random fill",
+            Generator::RecursiveBacktracker => "Recursive backtracker",
+            Generator::Prim => "Randomized Prim",
+            Generator::Kruskal => "Randomized Kruskal",
+            Generator::Wilson => "Wilson",
+            Generator::Eller => "Eller",
+            Generator::RecursiveDivision => "Recursive division",
+            Generator::Cave => "Cellular automata cave",
+            Generator::Terrain => "Value noise terrain"
+        }
+    }
+
+
+    // Name given to --generator, also used to name the benchmark maps
+    pub fn id(&self) -> &'static str {
+        match self {
+            Generator::RandomFill => "random",
+            Generator::RecursiveBacktracker => "backtracker",
+            Generator::Prim => "prim",
+            Generator::Kruskal => "kruskal",
+            Generator::Wilson => "wilson",
+            Generator::Eller => "eller",
+            Generator::RecursiveDivision => "division",
+            Generator::Cave => "cave",
+            Generator::Terrain => "terrain"
+        }
+    }
+
+
+    pub fn from_id(id: &str) -> Option<Generator> {
+        Generator::ALL.into_iter().find(|generator| generator.id() == id)
+    }
+
+
+    // Cycled through with the N key
+    pub fn next(&self) -> Generator {
+        let index = Generator::ALL.iter().position(|generator| generator == self).unwrap();
+        Generator::ALL[(index + 1) % Generator::ALL.len()]
+    }
+}
+
+
+// Knobs of the generators that take any
+#[derive(Clone, Copy)]
+pub struct GeneratorOptions {
+    // Chance of a cave square starting as a wall
+    pub density: f64,
+    // Smoothing passes of the cave, noise octaves of the terrain
+    pub iterations: usize,
+    // Fraction of the dead ends opened into loops afterwards, from 0 to 1
+    pub braid: f64,
+}
+
+
+impl Default for GeneratorOptions {
+    fn default() -> GeneratorOptions {
+        GeneratorOptions {
+            density: 0.45,
+            iterations: 4,
+            braid: 0.0,
+        }
+    }
+}
+
+
+// A generated map and how it was built, so generation can be replayed
+pub struct Generation {
+    pub grid: Grid,
+    pub initial: Grid,
+    // Squares changed by every step, in order
+    pub steps: Vec<Vec<((usize, usize), Cell)>>,
+}
+
+
+// Applies and records the changes made by a generator
+struct Recorder {
+    grid: Grid,
+    initial: Grid,
+    steps: Vec<Vec<((usize, usize), Cell)>>,
+}
+
+
+impl Recorder {
+    fn new(initial: Grid) -> Recorder {
+        Recorder {
+            grid: initial.clone(),
+            initial,
+            steps: Vec::new(),
+        }
+    }
+
+
+    fn step(&mut self, changes: Vec<((usize, usize), Cell)>) {
+        for (pos, cell) in &changes {
+            self.grid.set_cell(*pos, *cell);
+        }
+        self.steps.push(changes);
+    }
+
+
+    fn finish(self) -> Generation {
+        Generation {
+            grid: self.grid,
+            initial: self.initial,
+            steps: self.steps,
+        }
+    }
+}
+
+
+// The same generator, size, options and seed always build the same map
+pub fn generate(generator: Generator, width: usize, height: usize, seed: u64, options: &GeneratorOptions) -> Generation {
+    let rng = &mut StdRng::seed_from_u64(seed);
+    let mut generation = match generator {
+        Generator::RandomFill => {
+            let mut grid = Grid::new(width, height);
+            random_fill(&mut grid, rng);
+            Recorder::new(grid).finish()
+        },
+        Generator::RecursiveBacktracker => recursive_backtracker(width, height, rng),
+        Generator::Prim => prim(width, height, rng),
+        Generator::Kruskal => kruskal(width, height, rng),
+        Generator::Wilson => wilson(width, height, rng),
+        Generator::Eller => eller(width, height, rng),
+        Generator::RecursiveDivision => recursive_division(width, height, rng),
+        Generator::Cave => cave(width, height, options, rng),
+        Generator::Terrain => terrain(width, height, options, rng)
+    };
+    if options.braid > 0.0 {
+        braid(&mut generation, options.braid, rng);
+    }
+    generation
+}
+
+
+// Short enough to be read from the screen and typed back
+pub fn random_seed() -> u64 {
+    rand::random::<u32>() as u64
+}
+
+
+// Every square becomes a wall with a 40% chance
+pub fn random_fill<R: Rng>(grid: &mut Grid, rng: &mut R) {
+    for x in 0..grid.width() {
+        for y in 0..grid.height() {
+            let cell = if rng.gen_bool(0.4) { Cell::Wall } else { Cell::Blank };
+            grid.set_cell((x, y), cell);
+        }
+    }
+}
+
+
+// Perfect mazes are built on maze cells placed on the even squares of the
+// grid. The odd squares between two cells are the walls that get carved.
+fn maze_size(width: usize, height: usize) -> (usize, usize) {
+    (width.div_ceil(2), height.div_ceil(2))
+}
+
+
+fn to_square(cell: (usize, usize)) -> (usize, usize) {
+    (cell.0 * 2, cell.1 * 2)
+}
+
+
+// Square between two adjacent maze cells
+fn between(a: (usize, usize), b: (usize, usize)) -> (usize, usize) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+
+fn maze_neighbors(cell: (usize, usize), size: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    if cell.1 != 0 {
+        result.push((cell.0, cell.1 - 1));
+    }
+    if cell.1 + 1 < size.1 {
+        result.push((cell.0, cell.1 + 1));
+    }
+    if cell.0 + 1 < size.0 {
+        result.push((cell.0 + 1, cell.1));
+    }
+    if cell.0 != 0 {
+        result.push((cell.0 - 1, cell.1));
+    }
+    result
+}
+
+
+fn filled_grid(width: usize, height: usize) -> Grid {
+    let mut grid = Grid::new(width, height);
+    for x in 0..width {
+        for y in 0..height {
+            grid.set_cell((x, y), Cell::Wall);
+        }
+    }
+    grid
+}
+
+
+fn random_cell<R: Rng>(size: (usize, usize), rng: &mut R) -> (usize, usize) {
+    (rng.gen_range(0..size.0), rng.gen_range(0..size.1))
+}
+
+
+// Carves a passage from one cell into an adjacent one
+fn carve(from: (usize, usize), to: (usize, usize)) -> Vec<((usize, usize), Cell)> {
+    vec![(between(from, to), Cell::Blank), (to_square(to), Cell::Blank)]
+}
+
+
+// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Randomized_depth-first_search
+fn recursive_backtracker<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    let mut recorder = Recorder::new(filled_grid(width, height));
+    let mut visited = vec![vec![false; size.1]; size.0];
+    let start = random_cell(size, rng);
+    visited[start.0][start.1] = true;
+    recorder.step(vec![(to_square(start), Cell::Blank)]);
+
+    let mut stack = vec![start];
+    while let Some(&current) = stack.last() {
+        let unvisited: Vec<(usize, usize)> = maze_neighbors(current, size)
+            .into_iter()
+            .filter(|cell| !visited[cell.0][cell.1])
+            .collect();
+        match unvisited.choose(rng) {
+            Some(&next) => {
+                visited[next.0][next.1] = true;
+                recorder.step(carve(current, next));
+                stack.push(next);
+            },
+            None => { stack.pop(); }
+        }
+    }
+    recorder.finish()
+}
+
+
+// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Iterative_randomized_Prim's_algorithm_(without_stack,_without_sets)
+fn prim<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    let mut recorder = Recorder::new(filled_grid(width, height));
+    let mut in_maze = vec![vec![false; size.1]; size.0];
+    let start = random_cell(size, rng);
+    in_maze[start.0][start.1] = true;
+    recorder.step(vec![(to_square(start), Cell::Blank)]);
+
+    // Cells next to the maze and the maze cell they would connect to
+    let mut frontier: Vec<((usize, usize), (usize, usize))> = maze_neighbors(start, size).into_iter().map(|cell| (cell, start)).collect();
+    while !frontier.is_empty() {
+        let (cell, from) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if in_maze[cell.0][cell.1] { continue }
+        in_maze[cell.0][cell.1] = true;
+        recorder.step(carve(from, cell));
+        for next in maze_neighbors(cell, size) {
+            if !in_maze[next.0][next.1] {
+                frontier.push((next, cell));
+            }
+        }
+    }
+    recorder.finish()
+}
+
+
+fn find_set(sets: &mut Vec<usize>, i: usize) -> usize {
+    if sets[i] != i {
+        sets[i] = find_set(sets, sets[i]);
+    }
+    sets[i]
+}
+
+
+// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Iterative_randomized_Kruskal's_algorithm_(with_sets)
+fn kruskal<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    let mut initial = filled_grid(width, height);
+    let mut edges: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for x in 0..size.0 {
+        for y in 0..size.1 {
+            initial.set_cell(to_square((x, y)), Cell::Blank);
+            if x + 1 < size.0 {
+                edges.push(((x, y), (x + 1, y)));
+            }
+            if y + 1 < size.1 {
+                edges.push(((x, y), (x, y + 1)));
+            }
+        }
+    }
+    let mut recorder = Recorder::new(initial);
+    edges.shuffle(rng);
+
+    let index = |cell: (usize, usize)| cell.0 * size.1 + cell.1;
+    let mut sets: Vec<usize> = (0..size.0 * size.1).collect();
+    for (a, b) in edges {
+        let set_a = find_set(&mut sets, index(a));
+        let set_b = find_set(&mut sets, index(b));
+        if set_a != set_b {
+            sets[set_b] = set_a;
+            recorder.step(vec![(between(a, b), Cell::Blank)]);
+        }
+    }
+    recorder.finish()
+}
+
+
+// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Wilson's_algorithm
+fn wilson<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    let mut recorder = Recorder::new(filled_grid(width, height));
+    let mut in_maze = vec![vec![false; size.1]; size.0];
+    let first = random_cell(size, rng);
+    in_maze[first.0][first.1] = true;
+    recorder.step(vec![(to_square(first), Cell::Blank)]);
+
+    let mut cells: Vec<(usize, usize)> = (0..size.0).flat_map(|x| (0..size.1).map(move |y| (x, y))).collect();
+    cells.shuffle(rng);
+    for cell in cells {
+        if in_maze[cell.0][cell.1] { continue }
+        // Random walk until the maze is hit. Keeping only the last exit of
+        // every cell erases the loops of the walk.
+        let mut exits: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        let mut current = cell;
+        while !in_maze[current.0][current.1] {
+            let next = *maze_neighbors(current, size).choose(rng).unwrap();
+            exits.insert(current, next);
+            current = next;
+        }
+
+        let mut changes = vec![(to_square(cell), Cell::Blank)];
+        current = cell;
+        while !in_maze[current.0][current.1] {
+            in_maze[current.0][current.1] = true;
+            let next = exits[&current];
+            changes.extend(carve(current, next));
+            current = next;
+        }
+        recorder.step(changes);
+    }
+    recorder.finish()
+}
+
+
+// http://www.neocomputer.org/projects/eller.html
+fn eller<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    let mut recorder = Recorder::new(filled_grid(width, height));
+    let mut next_set = 0usize;
+    let mut row_sets: Vec<Option<usize>> = vec![None; size.0];
+    for y in 0..size.1 {
+        let last_row = y + 1 == size.1;
+        let mut changes: Vec<((usize, usize), Cell)> = Vec::new();
+        let mut sets: Vec<usize> = Vec::new();
+        for (x, set) in row_sets.iter().enumerate() {
+            sets.push(set.unwrap_or_else(|| {
+                next_set += 1;
+                next_set
+            }));
+            changes.push((to_square((x, y)), Cell::Blank));
+        }
+
+        // Join adjacent cells of different sets, all of them in the last row
+        for x in 0..size.0.saturating_sub(1) {
+            if sets[x] != sets[x + 1] && (last_row || rng.gen_bool(0.5)) {
+                let (kept, merged) = (sets[x], sets[x + 1]);
+                for set in sets.iter_mut() {
+                    if *set == merged {
+                        *set = kept;
+                    }
+                }
+                changes.push((between((x, y), (x + 1, y)), Cell::Blank));
+            }
+        }
+        recorder.step(changes);
+        if last_row { break }
+
+        // Every set goes down at least once
+        let mut changes: Vec<((usize, usize), Cell)> = Vec::new();
+        row_sets = vec![None; size.0];
+        let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (x, set) in sets.iter().enumerate() {
+            members.entry(*set).or_default().push(x);
+        }
+        let mut groups: Vec<(usize, Vec<usize>)> = members.into_iter().collect();
+        groups.sort();
+        for (set, mut xs) in groups {
+            xs.shuffle(rng);
+            let down = 1 + (1..xs.len()).filter(|_| rng.gen_bool(0.3)).count();
+            for x in &xs[..down] {
+                row_sets[*x] = Some(set);
+                changes.push((between((*x, y), (*x, y + 1)), Cell::Blank));
+            }
+        }
+        recorder.step(changes);
+    }
+    recorder.finish()
+}
+
+
+// https://en.wikipedia.org/wiki/Maze_generation_algorithm#Recursive_division_method
+fn recursive_division<R: Rng>(width: usize, height: usize, rng: &mut R) -> Generation {
+    let size = maze_size(width, height);
+    // Open space inside the maze cells, walls on the extra row and column
+    let mut initial = filled_grid(width, height);
+    for x in 0..size.0 * 2 - 1 {
+        for y in 0..size.1 * 2 - 1 {
+            initial.set_cell((x, y), Cell::Blank);
+        }
+    }
+    let mut recorder = Recorder::new(initial);
+
+    // Regions in maze cells, first and last included
+    let mut regions: Vec<((usize, usize), (usize, usize))> = vec![((0, 0), (size.0 - 1, size.1 - 1))];
+    while let Some(((x0, y0), (x1, y1))) = regions.pop() {
+        let region_width = x1 - x0 + 1;
+        let region_height = y1 - y0 + 1;
+        if region_width < 2 || region_height < 2 { continue }
+        let horizontal = match region_height.cmp(&region_width) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => rng.gen_bool(0.5)
+        };
+
+        let mut changes: Vec<((usize, usize), Cell)> = Vec::new();
+        if horizontal {
+            // Wall below row y, with a gap at column gap
+            let y = rng.gen_range(y0..y1);
+            let gap = rng.gen_range(x0..=x1);
+            for square_x in x0 * 2..=x1 * 2 {
+                if square_x != gap * 2 {
+                    changes.push(((square_x, y * 2 + 1), Cell::Wall));
+                }
+            }
+            regions.push(((x0, y0), (x1, y)));
+            regions.push(((x0, y + 1), (x1, y1)));
+        } else {
+            let x = rng.gen_range(x0..x1);
+            let gap = rng.gen_range(y0..=y1);
+            for square_y in y0 * 2..=y1 * 2 {
+                if square_y != gap * 2 {
+                    changes.push(((x * 2 + 1, square_y), Cell::Wall));
+                }
+            }
+            regions.push(((x0, y0), (x, y1)));
+            regions.push(((x + 1, y0), (x1, y1)));
+        }
+        recorder.step(changes);
+    }
+    recorder.finish()
+}
+
+
+// https://www.roguebasin.com/index.php/Cellular_Automata_Method_for_Generating_Random_Cave-Like_Levels
+fn cave<R: Rng>(width: usize, height: usize, options: &GeneratorOptions, rng: &mut R) -> Generation {
+    let mut initial = Grid::new(width, height);
+    for x in 0..width {
+        for y in 0..height {
+            if rng.gen_bool(options.density.clamp(0.0, 1.0)) {
+                initial.set_cell((x, y), Cell::Wall);
+            }
+        }
+    }
+    let mut recorder = Recorder::new(initial);
+
+    // A square with more than 4 walls around becomes a wall, with less
+    // than 4 it is opened. Squares outside the map count as walls.
+    for _ in 0..options.iterations {
+        let mut changes: Vec<((usize, usize), Cell)> = Vec::new();
+        for x in 0..width {
+            for y in 0..height {
+                let mut walls = 0;
+                for dx in -1isize..=1 {
+                    for dy in -1isize..=1 {
+                        if dx == 0 && dy == 0 { continue }
+                        let (nx, ny) = (x as isize + dx, y as isize + dy);
+                        let outside = nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize;
+                        if outside || !recorder.grid.is_walkable((nx as usize, ny as usize)) {
+                            walls += 1;
+                        }
+                    }
+                }
+                let is_wall = !recorder.grid.is_walkable((x, y));
+                if walls > 4 && !is_wall {
+                    changes.push(((x, y), Cell::Wall));
+                } else if walls < 4 && is_wall {
+                    changes.push(((x, y), Cell::Blank));
+                }
+            }
+        }
+        if changes.is_empty() { break }
+        recorder.step(changes);
+    }
+    recorder.finish()
+}
+
+
+// Random values on a lattice, smoothly interpolated in between.
+// Every octave halves the lattice spacing and the amplitude.
+fn value_noise_octave<R: Rng>(width: usize, height: usize, spacing: usize, rng: &mut R) -> Vec<Vec<f64>> {
+    let lattice_width = width / spacing + 2;
+    let lattice_height = height / spacing + 2;
+    let lattice: Vec<Vec<f64>> = (0..lattice_width).map(|_| (0..lattice_height).map(|_| rng.gen::<f64>()).collect()).collect();
+    let smoothstep = |t: f64| t * t * (3.0 - 2.0 * t);
+    let mut values = vec![vec![0.0; height]; width];
+    for (x, column) in values.iter_mut().enumerate() {
+        for (y, value) in column.iter_mut().enumerate() {
+            let (lx, ly) = (x / spacing, y / spacing);
+            let tx = smoothstep((x % spacing) as f64 / spacing as f64);
+            let ty = smoothstep((y % spacing) as f64 / spacing as f64);
+            let top = lattice[lx][ly] + (lattice[lx + 1][ly] - lattice[lx][ly]) * tx;
+            let bottom = lattice[lx][ly + 1] + (lattice[lx + 1][ly + 1] - lattice[lx][ly + 1]) * tx;
+            *value = top + (bottom - top) * ty;
+        }
+    }
+    values
+}
+
+
+// Weighted squares from value noise, the lowest noise costing 1 and the highest MAX_COST.
+// https://en.wikipedia.org/wiki/Value_noise
+fn terrain<R: Rng>(width: usize, height: usize, options: &GeneratorOptions, rng: &mut R) -> Generation {
+    let mut recorder = Recorder::new(Grid::new(width, height));
+    let mut noise = vec![vec![0.0; height]; width];
+    let mut amplitude = 1.0;
+    let mut spacing = 8usize;
+    for _ in 0..options.iterations.max(1) {
+        let octave = value_noise_octave(width, height, spacing, rng);
+        for (column, octave_column) in noise.iter_mut().zip(&octave) {
+            for (value, octave_value) in column.iter_mut().zip(octave_column) {
+                *value += octave_value * amplitude;
+            }
+        }
+        amplitude /= 2.0;
+        spacing = (spacing / 2).max(1);
+
+        // Stretched to the whole range of costs after every octave
+        let min = noise.iter().flatten().cloned().fold(f64::MAX, f64::min);
+        let max = noise.iter().flatten().cloned().fold(f64::MIN, f64::max);
+        let mut changes: Vec<((usize, usize), Cell)> = Vec::new();
+        for (x, column) in noise.iter().enumerate() {
+            for (y, value) in column.iter().enumerate() {
+                let t = if max > min { (value - min) / (max - min) } else { 0.0 };
+                let cost = 1 + (t * (MAX_COST - 1) as f64).round() as u8;
+                let cell = if cost == 1 { Cell::Blank } else { Cell::Weighted(cost) };
+                if recorder.grid.get_cell((x, y)) != cell {
+                    changes.push(((x, y), cell));
+                }
+            }
+        }
+        if !changes.is_empty() {
+            recorder.step(changes);
+        }
+    }
+    recorder.finish()
+}
+
+
+// Walkable squares with a single walkable neighbor
+fn dead_ends(grid: &Grid) -> Vec<(usize, usize)> {
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    for x in 0..grid.width() {
+        for y in 0..grid.height() {
+            if grid.is_walkable((x, y)) && grid.neighbors((x, y)).len() == 1 {
+                result.push((x, y));
+            }
+        }
+    }
+    result
+}
+
+
+// Opens the given fraction of dead ends by removing a wall between them and
+// another passage, preferring passages that are dead ends too. Every opened
+// wall is a step of its own.
+// https://www.astrolog.org/labyrnth/algrithm.htm
+fn braid<R: Rng>(generation: &mut Generation, fraction: f64, rng: &mut R) {
+    let grid = &mut generation.grid;
+    let mut candidates = dead_ends(grid);
+    candidates.shuffle(rng);
+    let count = (candidates.len() as f64 * fraction.clamp(0.0, 1.0)).round() as usize;
+    for pos in candidates.into_iter().take(count) {
+        // An earlier opening may have removed this dead end already
+        if grid.neighbors(pos).len() != 1 { continue }
+        let mut walls: Vec<((usize, usize), (usize, usize))> = Vec::new();
+        for (dx, dy) in [(0isize, -1isize), (0, 1), (1, 0), (-1, 0)] {
+            let wall = (pos.0 as isize + dx, pos.1 as isize + dy);
+            let beyond = (pos.0 as isize + 2 * dx, pos.1 as isize + 2 * dy);
+            if beyond.0 < 0 || beyond.1 < 0 { continue }
+            let (wall, beyond) = ((wall.0 as usize, wall.1 as usize), (beyond.0 as usize, beyond.1 as usize));
+            if grid.contains(beyond) && !grid.is_walkable(wall) && grid.is_walkable(beyond) {
+                walls.push((wall, beyond));
+            }
+        }
+        let preferred: Vec<((usize, usize), (usize, usize))> = walls
+            .iter()
+            .filter(|(_, beyond)| grid.neighbors(*beyond).len() == 1)
+            .cloned()
+            .collect();
+        let options = if preferred.is_empty() { &walls } else { &preferred };
+        if let Some(&(wall, _)) = options.choose(rng) {
+            grid.set_cell(wall, Cell::Blank);
+            generation.steps.push(vec![(wall, Cell::Blank)]);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    const PERFECT: [Generator; 6] = [Generator::RecursiveBacktracker, Generator::Prim, Generator::Kruskal, Generator::Wilson, Generator::Eller, Generator::RecursiveDivision];
+
+
+    fn open_squares(grid: &Grid) -> Vec<(usize, usize)> {
+        (0..grid.width()).flat_map(|x| (0..grid.height()).map(move |y| (x, y))).filter(|sq| grid.is_walkable(*sq)).collect()
+    }
+
+
+    fn cells(grid: &Grid) -> Vec<Cell> {
+        (0..grid.width()).flat_map(|x| (0..grid.height()).map(move |y| (x, y))).map(|sq| grid.get_cell(sq)).collect()
+    }
+
+
+    fn assert_connected(grid: &Grid, generator: Generator) {
+        let components = grid.strong_components();
+        assert_eq!(components.len(), 1, "{} left {} groups", generator.name(), components.len());
+        assert_eq!(components[0].len(), open_squares(grid).len());
+    }
+
+
+    #[test]
+    fn perfect_mazes_are_one_tree() {
+        for generator in PERFECT {
+            for (width, height) in [(32, 18), (21, 15)] {
+                for seed in 0..5 {
+                    let grid = generate(generator, width, height, seed, &GeneratorOptions::default()).grid;
+                    assert_connected(&grid, generator);
+                    // A tree has one passage less than it has squares
+                    let squares = open_squares(&grid);
+                    let passages = squares.iter().map(|sq| grid.neighbors(*sq).len()).sum::<usize>() / 2;
+                    assert_eq!(passages + 1, squares.len(), "{} has loops", generator.name());
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn braiding_keeps_mazes_connected() {
+        for generator in PERFECT {
+            for braid in [0.5, 1.0] {
+                let options = GeneratorOptions { braid, ..GeneratorOptions::default() };
+                let perfect = generate(generator, 32, 18, 7, &GeneratorOptions::default()).grid;
+                let braided = generate(generator, 32, 18, 7, &options).grid;
+                assert_connected(&braided, generator);
+                assert!(dead_ends(&braided).len() < dead_ends(&perfect).len(), "{} kept its dead ends", generator.name());
+            }
+        }
+    }
+
+
+    #[test]
+    fn seeds_reproduce_maps() {
+        let options = GeneratorOptions { braid: 0.3, ..GeneratorOptions::default() };
+        for generator in Generator::ALL {
+            let first = generate(generator, 32, 18, 42, &options).grid;
+            let again = generate(generator, 32, 18, 42, &options).grid;
+            assert!(cells(&first) == cells(&again), "{} changed with the same seed", generator.name());
+            let other = generate(generator, 32, 18, 43, &options).grid;
+            assert!(cells(&first) != cells(&other), "{} ignored the seed", generator.name());
+        }
+    }
+}
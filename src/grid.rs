@@ -0,0 +1,612 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+
+// Walkable layout of a map, with nothing about how it is drawn.
+// Used by the search code so it can run with or without a window.
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cell {
+    Blank,
+    Wall,
+    // Walkable, but entering it costs from 2 to 9 instead of 1
+    Weighted(u8),
+    // Can only be entered and left moving the given way
+    OneWay(Direction)
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left
+}
+
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+
+    // Unit vector on the screen, y going down
+    pub fn vector(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0)
+        }
+    }
+
+
+    // Clockwise, for rotating a one-way cell
+    pub fn next(&self) -> Direction {
+        let index = Direction::ALL.iter().position(|direction| direction == self).unwrap();
+        Direction::ALL[(index + 1) % Direction::ALL.len()]
+    }
+}
+
+
+pub const MAX_COST: u8 = 9;
+
+
+// How cells touch each other. Hex grids use offset coordinates: with pointy
+// tops the odd rows are pushed half a cell right, with flat tops the odd
+// columns are pushed half a cell down.
+// https://www.redblobgames.com/grids/hexagons/#coordinates-offset
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Topology {
+    Square,
+    HexPointy,
+    HexFlat
+}
+
+
+impl Topology {
+    pub const ALL: [Topology; 3] = [Topology::Square, Topology::HexPointy, Topology::HexFlat];
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Square => "Square",
+            Topology::HexPointy => "Pointy top hex",
+            Topology::HexFlat => "Flat top hex"
+        }
+    }
+
+
+    // Name given to --topology in the settings, headless runner and benchmark
+    pub fn id(&self) -> &'static str {
+        match self {
+            Topology::Square => "square",
+            Topology::HexPointy => "pointy",
+            Topology::HexFlat => "flat"
+        }
+    }
+
+
+    pub fn from_id(id: &str) -> Option<Topology> {
+        Topology::ALL.into_iter().find(|topology| topology.id() == id)
+    }
+
+
+    // Cycled through with the T key
+    pub fn next(&self) -> Topology {
+        let index = Topology::ALL.iter().position(|topology| topology == self).unwrap();
+        Topology::ALL[(index + 1) % Topology::ALL.len()]
+    }
+
+
+    pub fn is_hex(&self) -> bool {
+        *self != Topology::Square
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Grid {
+    cells: Vec<Vec<Cell>>,
+    topology: Topology,
+    // The last column touches the first one and the last row the first one
+    wrap: bool,
+    // Both cells of every linked pair, each one pointing to the other
+    portals: HashMap<(usize, usize), (usize, usize)>,
+    portal_cost: usize,
+}
+
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            cells: vec![vec![Cell::Blank; height]; width],
+            topology: Topology::Square,
+            wrap: false,
+            portals: HashMap::new(),
+            portal_cost: 1,
+        }
+    }
+
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+
+    // Makes both cells adjacent to each other, whatever their distance
+    pub fn add_portal(&mut self, a: (usize, usize), b: (usize, usize)) {
+        self.portals.insert(a, b);
+        self.portals.insert(b, a);
+    }
+
+
+    pub fn portal_exit(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        self.portals.get(&pos).copied()
+    }
+
+
+    pub fn set_portal_cost(&mut self, cost: usize) {
+        self.portal_cost = cost;
+    }
+
+
+    pub fn width(&self) -> usize {
+        self.cells.len()
+    }
+
+
+    pub fn height(&self) -> usize {
+        self.cells.first().map_or(0, |column| column.len())
+    }
+
+
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        pos.0 < self.width() && pos.1 < self.height()
+    }
+
+
+    pub fn get_cell(&self, pos: (usize, usize)) -> Cell {
+        self.cells[pos.0][pos.1]
+    }
+
+
+    pub fn set_cell(&mut self, pos: (usize, usize), cell: Cell) {
+        self.cells[pos.0][pos.1] = cell;
+    }
+
+
+    pub fn is_walkable(&self, pos: (usize, usize)) -> bool {
+        self.get_cell(pos) != Cell::Wall
+    }
+
+
+    // Cost of moving from one cell into an adjacent one, paid on entering it.
+    // Walking never costs less than 1, so distance stays admissible; going
+    // through a portal costs portal_cost instead.
+    pub fn step_cost(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        let walk = match self.get_cell(to) {
+            Cell::Weighted(cost) => cost as usize,
+            _ => 1
+        };
+        if self.portal_exit(from) != Some(to) {
+            walk
+        } else if self.distance(from, to) == 1 {
+            walk.min(self.portal_cost)
+        } else {
+            self.portal_cost
+        }
+    }
+
+
+    pub fn manhattan_distance(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        from.0.abs_diff(to.0) + from.1.abs_diff(to.1)
+    }
+
+
+    // Fewest moves between two cells ignoring walls, so it never
+    // overestimates a path cost. Hex cells are compared in axial coordinates.
+    // https://www.redblobgames.com/grids/hexagons/#distances
+    pub fn distance(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        let (wrap_x, wrap_y) = self.wrapping_axes();
+        if !self.topology.is_hex() {
+            // Going around may be shorter along each axis
+            let (x_gap, y_gap) = (from.0.abs_diff(to.0), from.1.abs_diff(to.1));
+            let x_gap = if wrap_x { x_gap.min(self.width() - x_gap) } else { x_gap };
+            let y_gap = if wrap_y { y_gap.min(self.height() - y_gap) } else { y_gap };
+            return x_gap + y_gap
+        }
+        // Closest of the copies of the goal in the boards around this one
+        let copies = |wraps: bool| if wraps { -1..=1 } else { 0..=0 };
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let mut best = usize::MAX;
+        for x_copy in copies(wrap_x) {
+            for y_copy in copies(wrap_y) {
+                let copy = (to.0 as isize + x_copy * width, to.1 as isize + y_copy * height);
+                best = best.min(self.hex_distance(from, copy));
+            }
+        }
+        best
+    }
+
+
+    // Lower bound of the cost between two cells, with portals. A route using
+    // portals walks to one of them, pays at least one jump and walks from one
+    // of them to the goal, so it costs no less than the nearest portals on
+    // both ends plus a jump.
+    pub fn heuristic(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        let direct = self.distance(from, to);
+        let to_portal = self.portals.keys().map(|portal| self.distance(from, *portal)).min();
+        let from_portal = self.portals.keys().map(|portal| self.distance(*portal, to)).min();
+        match (to_portal, from_portal) {
+            (Some(to_portal), Some(from_portal)) => direct.min(to_portal + self.portal_cost + from_portal),
+            _ => direct
+        }
+    }
+
+
+    // Whether the grid wraps left to right and top to bottom. Hex grids
+    // only line up across their shifted rows or columns when there is an
+    // even number of them, so an odd number doesn't wrap that way.
+    fn wrapping_axes(&self) -> (bool, bool) {
+        if !self.wrap { return (false, false) }
+        match self.topology {
+            Topology::Square => (true, true),
+            Topology::HexPointy => (true, self.height().is_multiple_of(2)),
+            Topology::HexFlat => (self.width().is_multiple_of(2), true)
+        }
+    }
+
+
+    // Takes positions outside the grid too, for the copies of a wrapped grid
+    fn hex_distance(&self, from: (usize, usize), to: (isize, isize)) -> usize {
+        let (from_q, from_r) = self.to_axial((from.0 as isize, from.1 as isize));
+        let (to_q, to_r) = self.to_axial(to);
+        let (dq, dr) = (from_q - to_q, from_r - to_r);
+        ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as usize
+    }
+
+
+    fn to_axial(&self, pos: (isize, isize)) -> (isize, isize) {
+        let (x, y) = pos;
+        match self.topology {
+            Topology::HexFlat => (x, y - (x - (x & 1)) / 2),
+            _ => (x - (y - (y & 1)) / 2, y)
+        }
+    }
+
+
+    // Relative positions of the cells touching pos, which depend on whether
+    // its row or column is shifted on hex grids
+    fn offsets(&self, pos: (usize, usize)) -> &'static [(isize, isize)] {
+        match self.topology {
+            Topology::Square => &[(0, -1), (0, 1), (1, 0), (-1, 0)],
+            Topology::HexPointy if pos.1.is_multiple_of(2) => &[(0, -1), (-1, -1), (0, 1), (-1, 1), (1, 0), (-1, 0)],
+            Topology::HexPointy => &[(1, -1), (0, -1), (1, 1), (0, 1), (1, 0), (-1, 0)],
+            Topology::HexFlat if pos.0.is_multiple_of(2) => &[(0, -1), (0, 1), (1, -1), (1, 0), (-1, -1), (-1, 0)],
+            Topology::HexFlat => &[(0, -1), (0, 1), (1, 0), (1, 1), (-1, 0), (-1, 1)]
+        }
+    }
+
+
+    // Movement on the screen of a move by the given offset, in half cells.
+    // Moves to a shifted hex row or column go half a cell sideways.
+    fn screen_move(&self, from: (usize, usize), offset: (isize, isize)) -> (isize, isize) {
+        let (dx, dy) = offset;
+        match self.topology {
+            Topology::HexPointy if dy != 0 => (2 * dx + if from.1.is_multiple_of(2) { 1 } else { -1 }, 2 * dy),
+            Topology::HexFlat if dx != 0 => (2 * dx, 2 * dy + if from.0.is_multiple_of(2) { 1 } else { -1 }),
+            _ => (2 * dx, 2 * dy)
+        }
+    }
+
+
+    // One-way cells only let through moves going their way, which on hex
+    // grids includes the two diagonals on that side
+    fn allows_move(&self, pos: (usize, usize), screen_move: (isize, isize)) -> bool {
+        match self.get_cell(pos) {
+            Cell::OneWay(direction) => {
+                let (x, y) = direction.vector();
+                x * screen_move.0 + y * screen_move.1 > 0
+            },
+            _ => true
+        }
+    }
+
+
+    // Cells that can be reached from current in one move. On square grids
+    // they come in up, down, right, left order, followed by the other end of
+    // a portal. One-way cells make some moves impossible in one direction
+    // only, so a cell is not always a neighbor of its neighbors.
+    pub fn neighbors(&self, current: (usize, usize)) -> Vec<(usize, usize)> {
+        let (wrap_x, wrap_y) = self.wrapping_axes();
+        let mut result: Vec<(usize, usize)> = Vec::new();
+        for (dx, dy) in self.offsets(current) {
+            let x = current.0 as isize + dx;
+            let y = current.1 as isize + dy;
+            let x = if wrap_x { x.rem_euclid(self.width() as isize) } else { x };
+            let y = if wrap_y { y.rem_euclid(self.height() as isize) } else { y };
+            if x < 0 || y < 0 { continue }
+            let new = (x as usize, y as usize);
+            // Tiny wrapped grids reach the same cell from several sides
+            if !self.contains(new) || new == current || result.contains(&new) { continue }
+            let screen_move = self.screen_move(current, (*dx, *dy));
+            if self.is_walkable(new) && self.allows_move(current, screen_move) && self.allows_move(new, screen_move) {
+                result.push(new);
+            }
+        }
+        if let Some(exit) = self.portal_exit(current) {
+            if self.is_walkable(exit) && !result.contains(&exit) {
+                result.push(exit);
+            }
+        }
+        result
+    }
+
+
+    // Groups of walkable cells where every cell can reach every other one,
+    // the largest first. One-way cells only join a group when there is a way
+    // back to them.
+    // Kosaraju's algorithm: https://en.wikipedia.org/wiki/Kosaraju%27s_algorithm
+    pub fn strong_components(&self) -> Vec<Vec<(usize, usize)>> {
+        // Cells in the order their depth-first search finished
+        let mut finished: Vec<(usize, usize)> = Vec::new();
+        let mut seen = vec![vec![false; self.height()]; self.width()];
+        let mut incoming: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                if seen[x][y] || !self.is_walkable((x, y)) { continue }
+                seen[x][y] = true;
+                let mut stack = vec![((x, y), self.neighbors((x, y)))];
+                while let Some((current, next_cells)) = stack.last_mut() {
+                    let current = *current;
+                    match next_cells.pop() {
+                        Some(next) => {
+                            incoming.entry(next).or_default().push(current);
+                            if !seen[next.0][next.1] {
+                                seen[next.0][next.1] = true;
+                                stack.push((next, self.neighbors(next)));
+                            }
+                        },
+                        None => {
+                            finished.push(current);
+                            stack.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        // Going backwards from the last to finish only reaches its own group
+        let mut assigned: HashSet<(usize, usize)> = HashSet::new();
+        let mut result: Vec<Vec<(usize, usize)>> = Vec::new();
+        for root in finished.into_iter().rev() {
+            if !assigned.insert(root) { continue }
+            let mut component = vec![root];
+            let mut index = 0;
+            while index < component.len() {
+                for previous in incoming.get(&component[index]).into_iter().flatten() {
+                    if assigned.insert(*previous) {
+                        component.push(*previous);
+                    }
+                }
+                index += 1;
+            }
+            result.push(component);
+        }
+        result.sort_by_key(|component| std::cmp::Reverse(component.len()));
+        result
+    }
+
+
+    // Whether some path leads from one cell to the other, following one-way cells
+    pub fn can_reach(&self, from: (usize, usize), to: (usize, usize)) -> bool {
+        if !self.is_walkable(from) || !self.is_walkable(to) { return false }
+        let mut seen: HashSet<(usize, usize)> = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to { return true }
+            for next in self.neighbors(current) {
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    use super::*;
+
+
+    // Fewest moves from the source to every cell it can reach
+    fn moves_from(grid: &Grid, source: (usize, usize)) -> HashMap<(usize, usize), usize> {
+        let mut result: HashMap<(usize, usize), usize> = HashMap::from([(source, 0)]);
+        let mut queue = VecDeque::from([source]);
+        while let Some(current) = queue.pop_front() {
+            for next in grid.neighbors(current) {
+                if !result.contains_key(&next) {
+                    result.insert(next, result[&current] + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        result
+    }
+
+
+    fn cells(grid: &Grid) -> Vec<(usize, usize)> {
+        (0..grid.width()).flat_map(|x| (0..grid.height()).map(move |y| (x, y))).collect()
+    }
+
+
+    // Without one-way cells every move can be taken back, and distance is
+    // never more than the fewest moves
+    fn assert_symmetric_and_admissible(grid: &Grid) {
+        for from in cells(grid).into_iter().filter(|sq| grid.is_walkable(*sq)) {
+            for next in grid.neighbors(from) {
+                assert!(grid.neighbors(next).contains(&from), "{from:?} -> {next:?} can't be taken back on {:?}", grid.topology());
+            }
+            for (to, moves) in moves_from(grid, from) {
+                assert!(grid.distance(from, to) <= moves, "distance from {from:?} to {to:?} is over {moves} on {:?}", grid.topology());
+            }
+        }
+    }
+
+
+    #[test]
+    fn hex_neighbors_are_symmetric_and_distance_admissible() {
+        for topology in [Topology::HexPointy, Topology::HexFlat] {
+            for (width, height) in [(7, 6), (6, 7)] {
+                let mut grid = Grid::new(width, height);
+                grid.set_topology(topology);
+                assert_symmetric_and_admissible(&grid);
+                assert_eq!(grid.neighbors((3, 3)).len(), 6);
+            }
+        }
+    }
+
+
+    #[test]
+    fn hex_distance_is_exact_on_an_open_grid() {
+        for topology in [Topology::HexPointy, Topology::HexFlat] {
+            let mut grid = Grid::new(7, 7);
+            grid.set_topology(topology);
+            for from in cells(&grid) {
+                for (to, moves) in moves_from(&grid, from) {
+                    assert_eq!(grid.distance(from, to), moves);
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn wrapped_neighbors_are_symmetric_and_distance_admissible() {
+        for topology in Topology::ALL {
+            // Odd sizes don't wrap along the shifted hex axis
+            for (width, height) in [(6, 6), (7, 5), (2, 3), (1, 4)] {
+                let mut grid = Grid::new(width, height);
+                grid.set_topology(topology);
+                grid.set_wrap(true);
+                grid.set_cell((0, height / 2), Cell::Wall);
+                assert_symmetric_and_admissible(&grid);
+            }
+        }
+    }
+
+
+    #[test]
+    fn wrapping_reaches_the_other_edge_in_one_move() {
+        let mut grid = Grid::new(5, 4);
+        grid.set_wrap(true);
+        assert!(grid.neighbors((0, 0)).contains(&(4, 0)));
+        assert!(grid.neighbors((0, 0)).contains(&(0, 3)));
+        assert_eq!(grid.distance((0, 0), (4, 3)), 2);
+    }
+
+
+    // Cheapest path cost from the source to every cell it can reach
+    fn costs_from(grid: &Grid, source: (usize, usize)) -> HashMap<(usize, usize), usize> {
+        let mut result: HashMap<(usize, usize), usize> = HashMap::from([(source, 0)]);
+        let mut open_set = BinaryHeap::from([Reverse((0, source))]);
+        while let Some(Reverse((cost, current))) = open_set.pop() {
+            if cost > result[&current] { continue }
+            for next in grid.neighbors(current) {
+                let next_cost = cost + grid.step_cost(current, next);
+                if result.get(&next).is_none_or(|old| next_cost < *old) {
+                    result.insert(next, next_cost);
+                    open_set.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        result
+    }
+
+
+    #[test]
+    fn portal_heuristic_never_overestimates() {
+        for topology in Topology::ALL {
+            for portal_cost in [0, 1, 3, 20] {
+                let mut grid = Grid::new(9, 7);
+                grid.set_topology(topology);
+                grid.set_portal_cost(portal_cost);
+                grid.add_portal((0, 0), (8, 6));
+                grid.add_portal((4, 0), (1, 5));
+                grid.set_cell((5, 3), Cell::Weighted(5));
+                for y in 0..6 {
+                    grid.set_cell((6, y), Cell::Wall);
+                }
+                for from in cells(&grid).into_iter().filter(|sq| grid.is_walkable(*sq)) {
+                    for (to, cost) in costs_from(&grid, from) {
+                        assert!(grid.heuristic(from, to) <= cost, "heuristic from {from:?} to {to:?} is over {cost} on {topology:?}");
+                    }
+                }
+            }
+        }
+    }
+
+
+    fn one_way(grid: &mut Grid, cells: &[((usize, usize), Direction)]) {
+        for (sq, direction) in cells {
+            grid.set_cell(*sq, Cell::OneWay(*direction));
+        }
+    }
+
+
+    #[test]
+    fn one_way_loop_is_one_component() {
+        // A ring around a wall with a one-way cell going clockwise on every side
+        let mut grid = Grid::new(3, 3);
+        grid.set_cell((1, 1), Cell::Wall);
+        one_way(&mut grid, &[((1, 0), Direction::Right), ((2, 1), Direction::Down), ((1, 2), Direction::Left), ((0, 1), Direction::Up)]);
+        let components = grid.strong_components();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), 8);
+        assert!(grid.can_reach((1, 0), (0, 0)));
+        assert!(grid.neighbors((1, 0)).iter().all(|sq| *sq != (0, 0)));
+    }
+
+
+    #[test]
+    fn one_way_door_splits_the_rooms() {
+        // Two rooms joined by a door that only lets through moves to the right
+        let mut grid = Grid::new(7, 3);
+        for y in 0..3 {
+            grid.set_cell((3, y), Cell::Wall);
+        }
+        one_way(&mut grid, &[((3, 1), Direction::Right)]);
+        assert!(grid.can_reach((0, 0), (6, 2)));
+        assert!(!grid.can_reach((6, 2), (0, 0)));
+        assert!(!grid.can_reach((0, 0), (3, 0)));
+
+        let components = grid.strong_components();
+        // Both rooms and the door on its own, which nothing leads back to
+        let mut sizes: Vec<usize> = components.iter().map(|component| component.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, [1, 9, 9]);
+        let left = components.iter().find(|component| component.contains(&(0, 0))).unwrap();
+        assert!(!left.contains(&(6, 2)));
+        assert!(!left.contains(&(3, 1)));
+    }
+}
@@ -0,0 +1,314 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time;
+
+use crate::grid::Grid;
+
+
+// A move from a square into one of its neighbors, or from a cluster into the next
+type Crossing = ((usize, usize), (usize, usize));
+
+
+// A node of the abstract graph and the cost of getting there
+pub type Edge = ((usize, usize), usize);
+
+
+// Clusters are squares of the grid this many squares wide, the last ones
+// on each side being smaller when the grid is not a multiple of it
+pub struct Hierarchy {
+    pub cluster_size: usize,
+    // Squares where paths cross from one cluster into the next, sorted
+    pub nodes: Vec<(usize, usize)>,
+    // Edges of the abstract graph out of every node, with their cost. Edges
+    // into the next cluster are single moves, the others stay in a cluster.
+    pub edges: HashMap<(usize, usize), Vec<Edge>>,
+    pub duration: time::Duration,
+}
+
+
+pub struct HierarchicalResult {
+    // Start, the nodes the path goes through and the goal
+    pub abstract_path: Vec<(usize, usize)>,
+    // From start to goal, both included. Empty if there is no path.
+    pub path: Vec<(usize, usize)>,
+    pub cost: usize,
+    pub abstract_expanded: usize,
+    // Expanded linking the start and goal to the graph and refining the path
+    pub local_expanded: usize,
+    pub duration: time::Duration,
+}
+
+
+impl HierarchicalResult {
+    pub fn found(&self) -> bool {
+        !self.path.is_empty()
+    }
+
+
+    pub fn nodes_expanded(&self) -> usize {
+        self.abstract_expanded + self.local_expanded
+    }
+}
+
+
+// Squares a search reached without leaving the cluster of its source
+struct LocalSearch {
+    costs: HashMap<(usize, usize), usize>,
+    came_from: HashMap<(usize, usize), (usize, usize)>,
+    expanded: usize,
+}
+
+
+// Moves across a border that sit side by side, both ways on both sides, so
+// a path can slide from one to the other without leaving its clusters
+fn side_by_side(grid: &Grid, a: Crossing, b: Crossing) -> bool {
+    let close = |p: (usize, usize), q: (usize, usize)| p == q || (grid.neighbors(p).contains(&q) && grid.neighbors(q).contains(&p));
+    a != b && close(a.0, b.0) && close(a.1, b.1)
+}
+
+
+impl Hierarchy {
+    // Abstract graph of the grid: every run of moves across the border of two
+    // clusters gets one crossing in its middle, or one at each end when it
+    // is long, and the crossings in a cluster are linked by local searches
+    // Botea, Müller and Schaeffer, "Near Optimal Hierarchical Path-Finding" (2004).
+    pub fn build(grid: &Grid, cluster_size: usize) -> Hierarchy {
+        let time_start = time::Instant::now();
+        let mut hierarchy = Hierarchy { cluster_size: cluster_size.max(1), nodes: Vec::new(), edges: HashMap::new(), duration: time::Duration::ZERO };
+
+        // Every move into another cluster, by the two clusters
+        let mut crossings: HashMap<Crossing, Vec<Crossing>> = HashMap::new();
+        for x in 0..grid.width() {
+            for y in 0..grid.height() {
+                if !grid.is_walkable((x, y)) { continue }
+                for next in grid.neighbors((x, y)) {
+                    let (from, to) = (hierarchy.cluster_of((x, y)), hierarchy.cluster_of(next));
+                    if from != to {
+                        crossings.entry((from, to)).or_default().push(((x, y), next));
+                    }
+                }
+            }
+        }
+        let mut keys: Vec<Crossing> = crossings.keys().copied().collect();
+        keys.sort();
+        for key in keys {
+            let mut runs: Vec<Vec<Crossing>> = Vec::new();
+            for crossing in &crossings[&key] {
+                let (joined, mut apart): (Vec<_>, Vec<_>) = runs
+                    .into_iter()
+                    .partition(|run| run.iter().any(|other| side_by_side(grid, *crossing, *other)));
+                let mut run: Vec<Crossing> = joined.into_iter().flatten().collect();
+                run.push(*crossing);
+                apart.push(run);
+                runs = apart;
+            }
+            for mut run in runs {
+                run.sort();
+                let chosen = if run.len() < 6 { vec![run[run.len() / 2]] } else { vec![run[0], run[run.len() - 1]] };
+                for (from, to) in chosen {
+                    hierarchy.edges.entry(from).or_default().push((to, grid.step_cost(from, to)));
+                    hierarchy.nodes.extend([from, to]);
+                }
+            }
+        }
+        hierarchy.nodes.sort();
+        hierarchy.nodes.dedup();
+
+        let mut by_cluster: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+        for node in &hierarchy.nodes {
+            by_cluster.entry(hierarchy.cluster_of(*node)).or_default().push(*node);
+        }
+        for nodes in by_cluster.values() {
+            for from in nodes {
+                let local = hierarchy.local_search(grid, *from);
+                for to in nodes.iter().filter(|to| *to != from) {
+                    if let Some(cost) = local.costs.get(to) {
+                        hierarchy.edges.entry(*from).or_default().push((*to, *cost));
+                    }
+                }
+            }
+        }
+        hierarchy.duration = time_start.elapsed();
+        hierarchy
+    }
+
+
+    pub fn cluster_of(&self, sq: (usize, usize)) -> (usize, usize) {
+        (sq.0 / self.cluster_size, sq.1 / self.cluster_size)
+    }
+
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.values().map(|edges| edges.len()).sum()
+    }
+
+
+    // Dijkstra from the source without leaving its cluster
+    fn local_search(&self, grid: &Grid, source: (usize, usize)) -> LocalSearch {
+        let cluster = self.cluster_of(source);
+        let mut costs: HashMap<(usize, usize), usize> = HashMap::from([(source, 0)]);
+        let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        let mut open_set = BinaryHeap::from([Reverse((0, source))]);
+        let mut expanded = 0;
+        while let Some(Reverse((cost, current))) = open_set.pop() {
+            if cost > costs[&current] { continue }
+            expanded += 1;
+            for next in grid.neighbors(current) {
+                if self.cluster_of(next) != cluster { continue }
+                let next_cost = cost + grid.step_cost(current, next);
+                if costs.get(&next).is_none_or(|old| next_cost < *old) {
+                    costs.insert(next, next_cost);
+                    came_from.insert(next, current);
+                    open_set.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        LocalSearch { costs, came_from, expanded }
+    }
+
+
+    // Links the start and goal to the nodes of their clusters, searches the
+    // abstract graph with A* and refines every step of the abstract path
+    // with a local search. The path is close to the cheapest, not always it.
+    pub fn search(&self, grid: &Grid, start: (usize, usize), goal: (usize, usize)) -> HierarchicalResult {
+        let time_start = time::Instant::now();
+        let mut result = HierarchicalResult {
+            abstract_path: Vec::new(),
+            path: Vec::new(),
+            cost: 0,
+            abstract_expanded: 0,
+            local_expanded: 0,
+            duration: time::Duration::ZERO,
+        };
+        if !grid.is_walkable(start) || !grid.is_walkable(goal) {
+            result.duration = time_start.elapsed();
+            return result
+        }
+        let in_cluster = |cluster: (usize, usize)| self.nodes.iter().filter(move |node| self.cluster_of(**node) == cluster);
+
+        let local = self.local_search(grid, start);
+        result.local_expanded += local.expanded;
+        let mut from_start: Vec<Edge> = in_cluster(self.cluster_of(start))
+            .filter_map(|node| local.costs.get(node).map(|cost| (*node, *cost)))
+            .collect();
+        if let Some(cost) = local.costs.get(&goal) {
+            from_start.push((goal, *cost));
+        }
+        let mut to_goal: HashMap<(usize, usize), usize> = HashMap::new();
+        for node in in_cluster(self.cluster_of(goal)) {
+            let local = self.local_search(grid, *node);
+            result.local_expanded += local.expanded;
+            if let Some(cost) = local.costs.get(&goal) {
+                to_goal.insert(*node, *cost);
+            }
+        }
+
+        // A* over the abstract graph, the start and goal included
+        let mut open_set = BinaryHeap::from([Reverse((grid.heuristic(start, goal), start))]);
+        let mut path_costs: HashMap<(usize, usize), usize> = HashMap::from([(start, 0)]);
+        let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        while let Some(Reverse((score, current))) = open_set.pop() {
+            let path_cost = path_costs[&current];
+            if score > path_cost + grid.heuristic(current, goal) { continue }
+            result.abstract_expanded += 1;
+            if current == goal {
+                let mut node = goal;
+                result.abstract_path.push(node);
+                while let Some(previous) = came_from.get(&node) {
+                    result.abstract_path.push(*previous);
+                    node = *previous;
+                }
+                result.abstract_path.reverse();
+                break;
+            }
+            let mut edges: Vec<Edge> = self.edges.get(&current).cloned().unwrap_or_default();
+            if current == start {
+                edges.extend(from_start.iter().copied());
+            }
+            if let Some(cost) = to_goal.get(&current) {
+                edges.push((goal, *cost));
+            }
+            for (next, cost) in edges {
+                let next_cost = path_cost + cost;
+                if path_costs.get(&next).is_none_or(|old| next_cost < *old) {
+                    path_costs.insert(next, next_cost);
+                    came_from.insert(next, current);
+                    open_set.push(Reverse((next_cost + grid.heuristic(next, goal), next)));
+                }
+            }
+        }
+
+        // Moves into another cluster are taken as they are, the rest are
+        // searched again inside their cluster
+        if let Some(first) = result.abstract_path.first() {
+            result.path.push(*first);
+        }
+        for pair in result.abstract_path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if self.cluster_of(from) != self.cluster_of(to) {
+                result.path.push(to);
+                continue
+            }
+            let local = self.local_search(grid, from);
+            result.local_expanded += local.expanded;
+            let mut segment = vec![to];
+            while let Some(previous) = local.came_from.get(segment.last().unwrap()) {
+                segment.push(*previous);
+            }
+            segment.pop();
+            segment.reverse();
+            result.path.extend(segment);
+        }
+        result.cost = result.path.windows(2).map(|pair| grid.step_cost(pair[0], pair[1])).sum();
+        result.duration = time_start.elapsed();
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::{self, Generator, GeneratorOptions};
+    use crate::grid::Cell;
+    use crate::search::{self, Algorithm};
+
+
+    #[test]
+    fn paths_are_valid_and_never_cheaper_than_dijkstra() {
+        let options = GeneratorOptions { density: 0.45, iterations: 4, braid: 0.3 };
+        for (i, generator) in [Generator::RecursiveBacktracker, Generator::Cave, Generator::Terrain].into_iter().enumerate() {
+            let grid = generators::generate(generator, 30, 24, i as u64, &options).grid;
+            let hierarchy = Hierarchy::build(&grid, 8);
+            let components = grid.strong_components();
+            let largest = &components[0];
+            for j in 0..20 {
+                let start = largest[(j * 7) % largest.len()];
+                let goal = largest[(j * 13 + 5) % largest.len()];
+                let optimal = search::search(&grid, start, goal, Algorithm::Dijkstra);
+                let result = hierarchy.search(&grid, start, goal);
+                assert!(result.found(), "no path from {start:?} to {goal:?}");
+                assert_eq!(result.path.first(), Some(&start));
+                assert_eq!(result.path.last(), Some(&goal));
+                let mut cost = 0;
+                for pair in result.path.windows(2) {
+                    assert!(grid.neighbors(pair[0]).contains(&pair[1]), "{:?} to {:?} is not a move", pair[0], pair[1]);
+                    cost += grid.step_cost(pair[0], pair[1]);
+                }
+                assert_eq!(cost, result.cost);
+                assert!(result.cost >= optimal.cost);
+            }
+        }
+    }
+
+
+    #[test]
+    fn no_path_between_walled_off_squares() {
+        let mut grid = Grid::new(12, 12);
+        for y in 0..12 {
+            grid.set_cell((6, y), Cell::Wall);
+        }
+        let hierarchy = Hierarchy::build(&grid, 4);
+        assert!(!hierarchy.search(&grid, (0, 0), (11, 11)).found());
+    }
+}
@@ -0,0 +1,11 @@
+pub mod all_pairs;
+pub mod flow_field;
+pub mod generators;
+pub mod grid;
+pub mod hierarchical;
+pub mod map_file;
+pub mod multi_agent;
+pub mod placement;
+pub mod pursuit;
+pub mod search;
+pub mod space_time;
@@ -3,14 +3,22 @@ use std::time;
 use macroquad::prelude as mq;
 use macroquad::input as input_mq;
 
+use pathfinding::generators::{self, Generator, GeneratorOptions};
+use pathfinding::grid::Topology;
+use pathfinding::multi_agent::{Limit, Solver};
+use pathfinding::placement::Placement;
+use pathfinding::search::{Algorithm, Outcome};
+use pathfinding::all_pairs;
 
+
+mod playback;
 mod settings_reader;
 mod squares;
 
 
 // Setting up the window
 fn window_conf() -> mq::Conf {
-    let settings: HashMap<String, String> = settings_reader::get_settings();
+    let settings: &HashMap<String, String> = settings_reader::get_settings();
     mq::Conf {
         window_title: settings.get("window_title").unwrap().to_owned(),
         window_width: settings.get("window_width").unwrap().parse().unwrap(),
@@ -25,7 +33,7 @@ fn window_conf() -> mq::Conf {
 // Main function
 #[macroquad::main(window_conf)]
 async fn main() {
-    let settings: HashMap<String, String> = settings_reader::get_settings();
+    let settings: &HashMap<String, String> = settings_reader::get_settings();
 
     let square_width: usize = settings.get("square_width").unwrap().parse::<usize>().unwrap();
     let top_offset: usize = settings.get("top_offset").unwrap().parse::<usize>().unwrap();
@@ -35,36 +43,132 @@ async fn main() {
     let mut loop_start_time = time::Instant::now();
     let mut fps_counter: usize = 0usize;
     let mut frames_drawed: usize = 0usize;
+    let mut show_help = false;
+    let mut playback = playback::Playback::new(top_offset);
+    let mut generator = Generator::from_id(settings.get("generator").unwrap()).expect("unknown generator in the settings");
+    let generator_options = GeneratorOptions {
+        density: settings.get("density").unwrap().parse::<f64>().expect("the density must be a number"),
+        iterations: settings.get("iterations").unwrap().parse::<usize>().expect("the iterations must be a number"),
+        braid: settings.get("braid").unwrap().parse::<f64>().expect("the braid must be a number"),
+    };
+    let mut placement = Placement::from_id(settings.get("placement").unwrap()).expect("unknown placement in the settings");
+    let topology = Topology::from_id(settings.get("topology").unwrap()).expect("unknown topology in the settings");
+    square_collection.set_topology(topology);
+    if settings.get("wrap").unwrap().parse::<bool>().expect("wrap must be true or false") {
+        square_collection.toggle_wrap();
+    }
+    square_collection.set_portal_cost(settings.get("portal_cost").unwrap().parse::<usize>().expect("the portal cost must be a number"));
+    let flow_units = settings.get("flow_units").unwrap().parse::<usize>().expect("the flow units must be a number");
+    let cluster_size = settings.get("cluster_size").unwrap().parse::<usize>().expect("the cluster size must be a number");
+    // A seed given in the settings builds its maze right away
+    let mut seed = match settings.get("seed").unwrap().as_str() {
+        "random" => generators::random_seed(),
+        value => {
+            let seed = value.parse::<u64>().expect("the seed must be a number or random");
+            square_collection.generate_maze(generator, seed, &generator_options, placement, false);
+            seed
+        }
+    };
     // Window loop
     loop {
         // Background
         mq::clear_background(mq::WHITE);
 
         // Input
-        if input_mq::is_mouse_button_down(mq::MouseButton::Left) {
+        playback.update(&mut square_collection);
+        let editing = !square_collection.is_comparing() && !square_collection.is_measuring() && !playback.is_dragging();
+        if square_collection.is_measuring() && input_mq::is_mouse_button_pressed(mq::MouseButton::Left) {
+            square_collection.pick_distance_source(input_mq::mouse_position());
+        } else if editing && input_mq::is_mouse_button_down(mq::MouseButton::Left) {
             if input_mq::is_key_down(mq::KeyCode::LeftShift) {
                 square_collection.change_square_type(input_mq::mouse_position(), squares::SquareType::Blank);
             } else {
                 square_collection.change_square_type(input_mq::mouse_position(), squares::SquareType::Wall);
             }
-        } else if input_mq::is_mouse_button_down(mq::MouseButton::Right) {
+        } else if editing && input_mq::is_mouse_button_down(mq::MouseButton::Right) {
             square_collection.change_square_type(input_mq::mouse_position(), squares::SquareType::Objective);
-        } else if input_mq::is_mouse_button_down(mq::MouseButton::Middle) {
+        } else if editing && input_mq::is_mouse_button_down(mq::MouseButton::Middle) {
             square_collection.change_square_type(input_mq::mouse_position(), squares::SquareType::Start);
+        } else if editing && input_mq::is_key_pressed(mq::KeyCode::F) {
+            square_collection.rotate_one_way(input_mq::mouse_position());
+        } else if editing && input_mq::is_key_pressed(mq::KeyCode::K) {
+            let new_patrol = input_mq::is_key_down(mq::KeyCode::LeftShift);
+            square_collection.add_patrol_waypoint(input_mq::mouse_position(), new_patrol);
+        } else if editing && input_mq::is_key_pressed(mq::KeyCode::J) {
+            square_collection.place_agent(input_mq::mouse_position());
+        } else if editing && input_mq::is_key_pressed(mq::KeyCode::Q) {
+            square_collection.place_portal(input_mq::mouse_position());
         } else if input_mq::is_key_pressed(mq::KeyCode::A) {
-            square_collection.search_algorithm(true, true);
+            square_collection.search_algorithm(Algorithm::AStar);
         } else if input_mq::is_key_pressed(mq::KeyCode::D) {
-            square_collection.search_algorithm(true, false);
+            square_collection.search_algorithm(Algorithm::Dijkstra);
         } else if input_mq::is_key_pressed(mq::KeyCode::G) {
-            square_collection.search_algorithm(false, true);
+            square_collection.search_algorithm(Algorithm::GreedyBestFirst);
+        } else if input_mq::is_key_pressed(mq::KeyCode::S) {
+            square_collection.search_space_time();
+            playback.play(&mut square_collection);
+        } else if input_mq::is_key_pressed(mq::KeyCode::I) {
+            square_collection.search_hierarchical(cluster_size);
+        } else if input_mq::is_key_pressed(mq::KeyCode::B) {
+            square_collection.show_components();
+        } else if input_mq::is_key_pressed(mq::KeyCode::Y) {
+            square_collection.measure_distances();
+        } else if input_mq::is_key_pressed(mq::KeyCode::L) {
+            square_collection.spread_flow_field(flow_units, seed);
+            playback.play(&mut square_collection);
+        } else if input_mq::is_key_pressed(mq::KeyCode::U) {
+            let follow_patrol = input_mq::is_key_down(mq::KeyCode::LeftShift);
+            square_collection.pursue_target(follow_patrol, seed);
+            playback.play(&mut square_collection);
+        } else if input_mq::is_key_pressed(mq::KeyCode::X) {
+            let solver = if input_mq::is_key_down(mq::KeyCode::LeftShift) { Solver::Cooperative } else { Solver::ConflictBased };
+            square_collection.solve_agents(solver);
+            playback.play(&mut square_collection);
+        } else if input_mq::is_key_pressed(mq::KeyCode::V) {
+            if square_collection.is_comparing() {
+                square_collection.stop_comparing();
+            } else {
+                square_collection.compare_algorithms(&Algorithm::ALL);
+            }
         } else if input_mq::is_key_pressed(mq::KeyCode::C) {
             square_collection.clear();
         } else if input_mq::is_key_pressed(mq::KeyCode::M) {
-            square_collection.generate_maze();
+            let animate = input_mq::is_key_down(mq::KeyCode::LeftShift);
+            seed = generators::random_seed();
+            square_collection.generate_maze(generator, seed, &generator_options, placement, animate);
+            if animate {
+                playback.play(&mut square_collection);
+            }
+        } else if input_mq::is_key_pressed(mq::KeyCode::R) {
+            square_collection.generate_maze(generator, seed, &generator_options, placement, false);
+        } else if input_mq::is_key_pressed(mq::KeyCode::N) {
+            generator = generator.next();
+        } else if input_mq::is_key_pressed(mq::KeyCode::O) {
+            placement = placement.next();
+        } else if input_mq::is_key_pressed(mq::KeyCode::T) {
+            square_collection.set_topology(square_collection.get_topology().next());
+        } else if input_mq::is_key_pressed(mq::KeyCode::W) {
+            square_collection.toggle_wrap();
         } else if input_mq::is_key_pressed(mq::KeyCode::Right) {
             square_collection.load_next_state();
         } else if input_mq::is_key_pressed(mq::KeyCode::Left) {
             square_collection.load_previous_state();
+        } else if input_mq::is_key_pressed(mq::KeyCode::Home) {
+            square_collection.set_replay_state(0);
+        } else if input_mq::is_key_pressed(mq::KeyCode::End) {
+            square_collection.set_replay_state(usize::MAX);
+        } else if input_mq::is_key_pressed(mq::KeyCode::Space) {
+            playback.toggle(&mut square_collection);
+        } else if input_mq::is_key_pressed(mq::KeyCode::Up) {
+            playback.faster();
+        } else if input_mq::is_key_pressed(mq::KeyCode::Down) {
+            playback.slower();
+        } else if input_mq::is_key_pressed(mq::KeyCode::P) {
+            square_collection.toggle_parents();
+        } else if input_mq::is_key_pressed(mq::KeyCode::E) {
+            square_collection.cycle_heatmap();
+        } else if input_mq::is_key_pressed(mq::KeyCode::H) {
+            show_help = !show_help;
         }
 
         // FPS counter
@@ -80,7 +184,13 @@ async fn main() {
         
         // Draw
         square_collection.draw_squares();
-        draw_ui(square_width, top_offset, &text_to_draw);
+        draw_ui(top_offset, &text_to_draw);
+        draw_stats(&square_collection, generator, placement, seed);
+        playback.draw(&square_collection);
+        draw_inspector(&square_collection);
+        if show_help {
+            draw_help(top_offset);
+        }
 
 
         // FPS limit so it doesn't stress your CPU out
@@ -97,43 +207,307 @@ async fn main() {
 }
 
 
-// Draw grid
-fn draw_ui(square_width: usize, top_offset: usize, fps_counter: &str) {
-    // Grid
-    let mut x = 0usize;
-    let mut y = top_offset;
-    let thickness = 2f32;
-    let line_color = mq::BLACK;
-    let screen_width = mq::screen_width() as usize;
-    let screen_height = mq::screen_height() as usize;
-    while x <= screen_width {
-        mq::draw_line(x as f32, top_offset as f32, x as f32, mq::screen_height(), thickness, line_color);
-        x += square_width;
-    }
-    while y <= (screen_height as usize) {
-        mq::draw_line(0f32, y as f32, mq::screen_width(), y as f32, thickness, line_color);
-        y += square_width;
-    }
-
+// Draw the top panel
+fn draw_ui(top_offset: usize, fps_counter: &str) {
     let min_y = 25f32;
     let font_size = 30f32;
     // FPS
     mq::draw_text(fps_counter, 5f32, min_y, font_size, mq::BLACK);
 
-    // Controls
-    let x_clicks = 125f32;
-    mq::draw_text("LClick to create a wall", x_clicks, min_y + 0.0 * font_size, font_size, mq::BLACK);
-    mq::draw_text("RClick to create the goal", x_clicks, min_y + 1.0 * font_size, font_size, mq::BLACK);
-    mq::draw_text("MClick to create the start", x_clicks, min_y + 2.0 * font_size, font_size, mq::BLACK);
-    let x_algorithms = 480f32;
-    mq::draw_text("Press A for A* algorithm", x_algorithms, min_y + 0.0*font_size, font_size, mq::BLACK);
-    mq::draw_text("Press D for Dijkstra's algorithm", x_algorithms, min_y + 1.0 * font_size, font_size, mq::BLACK);
-    mq::draw_text("Press G for Greedy Best first algorithm", x_algorithms, min_y + 2.0 * font_size, font_size, mq::BLACK);
-    let x_extra = 1030f32;
-    mq::draw_text("Press C to clear the the board", x_extra, min_y + 0.0 * font_size, font_size, mq::BLACK);
-    mq::draw_text("Press M to generate a maze", x_extra, min_y + 1.0 * font_size, font_size, mq::BLACK);
-    mq::draw_text("Press arrow keys to go through the solution", x_extra, min_y + 2.0 * font_size, font_size, mq::BLACK);
-
     // Me
     mq::draw_text("@aleferu", 4f32, min_y + 1.5 * font_size, font_size, mq::BLACK);
+
+    mq::draw_text("Press H for controls", 4f32, min_y + 2.2 * font_size, font_size * 0.7, mq::DARKGRAY);
+
+    // The controls used the most, the rest are behind H. Between the stats
+    // and the replay timeline.
+    let controls = "LClick wall   RClick goal   MClick start   A / D / G search   M maze   C clear   Space play   Arrows step";
+    mq::draw_text(controls, 5f32, top_offset as f32 - 40f32, font_size * 0.7, mq::DARKGRAY);
+}
+
+
+// Numbers of the last search, next to the FPS counter
+fn draw_stats(square_collection: &squares::SquareCollection, generator: Generator, placement: Placement, seed: u64) {
+    let min_y = 25f32;
+    let font_size = 30f32;
+    mq::draw_text(&format!("Seed: {seed}"), 750f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+    mq::draw_text(&format!("Maze: {}", generator.name()), 1200f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+    if square_collection.is_comparing() {
+        let (step, total_steps) = square_collection.get_replay_position();
+        mq::draw_text("Comparing algorithms side by side, press V to go back", 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Replay step: {step} / {total_steps}"), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        return
+    }
+    if let Some(result) = square_collection.get_space_time_result() {
+        let (step, total_steps) = square_collection.get_replay_position();
+        mq::draw_text("Algorithm: Space-time A*", 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Time step: {} / {}", step - 1, total_steps - 1), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Search time: {:.3} ms", result.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Nodes expanded: {}", result.nodes_expanded), 750f32, min_y, font_size, mq::BLACK);
+        if result.found() {
+            mq::draw_text(&format!("Path cost: {}", result.cost), 1200f32, min_y, font_size, mq::BLACK);
+            mq::draw_text(&format!("Waits: {}", result.waits), 1200f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        } else if result.hit_max_time {
+            mq::draw_text(&format!("Gave up, no path within {} time steps", result.max_time), 1200f32, min_y, font_size, mq::BLACK);
+        } else {
+            mq::draw_text("No path found", 1200f32, min_y, font_size, mq::BLACK);
+        }
+        return
+    }
+    if let Some((hierarchy, result)) = square_collection.get_hierarchical_result() {
+        let size = hierarchy.cluster_size;
+        mq::draw_text("Algorithm: HPA*", 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Clusters: {size}x{size} squares"), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Search time: {:.3} ms", result.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Nodes expanded: {} ({} abstract)", result.nodes_expanded(), result.abstract_expanded), 750f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Graph: {} nodes, {} edges", hierarchy.nodes.len(), hierarchy.edge_count()), 750f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        if result.found() {
+            mq::draw_text(&format!("Path cost: {}", result.cost), 1200f32, min_y, font_size, mq::BLACK);
+        } else {
+            mq::draw_text("No path found", 1200f32, min_y, font_size, mq::BLACK);
+        }
+        mq::draw_text(&format!("Graph built in {:.3} ms", hierarchy.duration.as_secs_f64() * 1000.0), 1200f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        return
+    }
+    if square_collection.is_measuring() {
+        let table = match square_collection.get_distance_table() {
+            Some(table) => table,
+            None => {
+                mq::draw_text(&format!("Too many open squares for all-pairs distances, at most {}", all_pairs::ALL_PAIRS_LIMIT), 250f32, min_y, font_size, mq::BLACK);
+                mq::draw_text("Press Y to go back", 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+                return
+            }
+        };
+        mq::draw_text(&format!("Algorithm: {}", table.method.name()), 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Open squares: {}", table.squares.len()), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Search time: {:.3} ms", table.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Diameter: {}", table.diameter()), 750f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Average distance: {:.1}", table.average_distance()), 750f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        let source = match square_collection.get_distance_source() {
+            Some(source) => source,
+            None => {
+                mq::draw_text("Click a square to see its distances", 1200f32, min_y, font_size, mq::GRAY);
+                return
+            }
+        };
+        let reached = table.distances_from(source);
+        mq::draw_text(&format!("From ({}, {}): reaches {}", source.0, source.1, reached.len().saturating_sub(1)), 1200f32, min_y, font_size, mq::BLACK);
+        let hovered = square_collection.get_hovered_square(input_mq::mouse_position());
+        if let Some(sq) = hovered.filter(|sq| *sq != source) {
+            let distance = match table.distance(source, sq) {
+                Some(distance) => distance.to_string(),
+                None => "unreachable".to_string()
+            };
+            mq::draw_text(&format!("To ({}, {}): {distance}", sq.0, sq.1), 1200f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        }
+        return
+    }
+    if let Some(groups) = square_collection.get_component_groups() {
+        mq::draw_text(&format!("Connected components: {}", groups.len()), 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Largest: {} squares", groups.first().map_or(0, |group| group.len())), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        if let Some((start, goal)) = square_collection.get_endpoint_components() {
+            mq::draw_text(&format!("Start in component {}, goal in {}", start + 1, goal + 1), 750f32, min_y, font_size, mq::BLACK);
+        }
+        if square_collection.is_goal_unreachable() {
+            mq::draw_text("Unreachable: the start can't", 1200f32, min_y, font_size, mq::RED);
+            mq::draw_text("get to the goal, not searched", 1200f32, min_y + 1.0 * font_size, font_size, mq::RED);
+        }
+        return
+    }
+    if let Some(field) = square_collection.get_flow_field() {
+        let (step, total_steps) = square_collection.get_replay_position();
+        let (arrived, units) = square_collection.get_crowd_progress();
+        mq::draw_text("Algorithm: Flow field", 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Tick: {} / {}", step - 1, total_steps - 1), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Build time: {:.3} ms", field.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Squares reaching the goal: {}", field.costs.len()), 750f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Units arrived: {arrived} / {units}"), 750f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        let farthest = field.costs.values().max().unwrap_or(&0);
+        mq::draw_text(&format!("Highest cost: {farthest}"), 1200f32, min_y, font_size, mq::BLACK);
+        return
+    }
+    if let Some(result) = square_collection.get_multi_agent_result() {
+        let (step, _) = square_collection.get_replay_position();
+        mq::draw_text(&format!("Algorithm: {}", result.solver.name()), 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Time step: {} / {}", step - 1, result.makespan()), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Search time: {:.3} ms", result.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Nodes expanded: {}", result.nodes_expanded), 750f32, min_y, font_size, mq::BLACK);
+        if result.solver == Solver::ConflictBased {
+            mq::draw_text(&format!("Constraint tree nodes: {}", result.constraint_nodes), 750f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        }
+        if result.found() {
+            mq::draw_text(&format!("Sum of costs: {}", result.cost), 1200f32, min_y, font_size, mq::BLACK);
+            mq::draw_text(&format!("Makespan: {}", result.makespan()), 1200f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        } else if result.gave_up == Some(Limit::ConstraintNodes) {
+            mq::draw_text("Gave up, too many conflicts", 1200f32, min_y, font_size, mq::BLACK);
+        } else if result.gave_up == Some(Limit::TimeSteps) {
+            mq::draw_text(&format!("Gave up, no plan within {} time steps", result.max_time), 1200f32, min_y, font_size, mq::BLACK);
+        } else {
+            mq::draw_text("No joint plan found", 1200f32, min_y, font_size, mq::BLACK);
+        }
+        return
+    }
+    if let Some(result) = square_collection.get_pursuit_result() {
+        let (step, _) = square_collection.get_replay_position();
+        let tick = step - 1;
+        let replans = result.replans.iter().filter(|replan| **replan <= tick).count();
+        mq::draw_text(&format!("Algorithm: {} pursuit", result.algorithm.name()), 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Tick: {tick} / {}", result.ticks()), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Search time: {:.3} ms", result.duration.as_secs_f64() * 1000.0), 250f32, min_y + 2.0 * font_size, font_size, mq::BLACK);
+        mq::draw_text(&format!("Replans: {replans} / {}", result.replans.len()), 750f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Total nodes expanded: {}", result.nodes_expanded), 750f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        if result.caught {
+            mq::draw_text(&format!("Caught after {} ticks", result.ticks()), 1200f32, min_y, font_size, mq::BLACK);
+            mq::draw_text(&format!("Path cost: {}", result.cost), 1200f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        } else {
+            mq::draw_text("The goal got away", 1200f32, min_y, font_size, mq::BLACK);
+        }
+        return
+    }
+    if square_collection.is_generating() {
+        let (step, total_steps) = square_collection.get_replay_position();
+        mq::draw_text(&format!("Generating a maze with {}", generator.name()), 250f32, min_y, font_size, mq::BLACK);
+        mq::draw_text(&format!("Replay step: {step} / {total_steps}"), 250f32, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        return
+    }
+    if let Some(outcome) = square_collection.get_skipped_search() {
+        let hint = match outcome {
+            Outcome::MissingStart => "Middle click a square to place the start",
+            _ => "Right click a square to place the goal"
+        };
+        mq::draw_text(outcome.describe(), 250f32, min_y, font_size, mq::RED);
+        mq::draw_text(hint, 250f32, min_y + 1.0 * font_size, font_size, mq::GRAY);
+        return
+    }
+    let result = match square_collection.get_result() {
+        Some(result) => result,
+        None => {
+            mq::draw_text("Place the start and the goal, then pick an algorithm", 250f32, min_y, font_size, mq::GRAY);
+            mq::draw_text(&format!("Start and goal on new maps: {}", placement.name()), 250f32, min_y + 1.0 * font_size, font_size, mq::GRAY);
+            return
+        }
+    };
+    let (step, total_steps) = square_collection.get_replay_position();
+
+    let x_first = 250f32;
+    mq::draw_text(&format!("Algorithm: {}", result.algorithm.name()), x_first, min_y + 0.0 * font_size, font_size, mq::BLACK);
+    mq::draw_text(&format!("Replay step: {step} / {total_steps}"), x_first, min_y + 1.0 * font_size, font_size, mq::BLACK);
+    mq::draw_text(&format!("Search time: {:.3} ms", result.duration.as_secs_f64() * 1000.0), x_first, min_y + 2.0 * font_size, font_size, mq::BLACK);
+    let x_second = 750f32;
+    mq::draw_text(&format!("Nodes expanded: {}", result.nodes_expanded()), x_second, min_y + 0.0 * font_size, font_size, mq::BLACK);
+    mq::draw_text(&format!("Max open set size: {}", result.max_open_set), x_second, min_y + 1.0 * font_size, font_size, mq::BLACK);
+    let x_third = 1200f32;
+    match result.outcome() {
+        Outcome::Found { path, cost } => {
+            mq::draw_text(&format!("Path length: {}", path.len() - 1), x_third, min_y + 0.0 * font_size, font_size, mq::BLACK);
+            mq::draw_text(&format!("Path cost: {cost}"), x_third, min_y + 1.0 * font_size, font_size, mq::BLACK);
+        },
+        _ => mq::draw_text("No path found", x_third, min_y + 0.0 * font_size, font_size, mq::RED)
+    }
+}
+
+
+// Search values of the square under the mouse, next to it
+fn draw_inspector(square_collection: &squares::SquareCollection) {
+    let mouse_pos = input_mq::mouse_position();
+    let (result, sq) = match (square_collection.get_result(), square_collection.get_hovered_square(mouse_pos)) {
+        (Some(result), Some(sq)) => (result, sq),
+        _ => return
+    };
+    let g_score = match result.path_costs.get(&sq) {
+        Some(g_score) => g_score,
+        None => return
+    };
+    let h_score = result.heuristic_values[&sq];
+    let mut lines = vec![
+        format!("Square ({}, {})", sq.0, sq.1),
+        format!("g: {g_score}"),
+        if result.uses_heuristics() { format!("h: {h_score}") } else { format!("h: {h_score} (not used)") },
+        format!("f: {}", result.scores[&sq]),
+    ];
+    lines.push(match result.came_from.get(&sq) {
+        Some(parent) => format!("Parent: ({}, {})", parent.0, parent.1),
+        None => "Parent: none".to_string()
+    });
+    lines.push(match result.expanded_at.get(&sq) {
+        Some(index) => format!("Expanded at step {}", index + 1),
+        None => "Never expanded".to_string()
+    });
+
+    let font_size = 24f32;
+    let width = lines
+        .iter()
+        .map(|line| mq::measure_text(line, None, font_size as u16, 1.0).width)
+        .fold(0f32, f32::max) + 20f32;
+    let height = (lines.len() as f32 + 0.5) * font_size;
+    // Keep the box inside the window
+    let x = (mouse_pos.0 + 16f32).min(mq::screen_width() - width);
+    let y = (mouse_pos.1 + 16f32).min(mq::screen_height() - height);
+    mq::draw_rectangle(x, y, width, height, mq::Color::new(1.0, 1.0, 0.9, 0.95));
+    mq::draw_rectangle_lines(x, y, width, height, 2f32, mq::BLACK);
+    for (i, line) in lines.iter().enumerate() {
+        mq::draw_text(line, x + 10f32, y + (i as f32 + 1.0) * font_size, font_size, mq::BLACK);
+    }
+}
+
+
+// Controls, drawn on top of the grid
+fn draw_help(top_offset: usize) {
+    let controls = [
+        "Left Click to create a wall",
+        "Left Shift + Left Click to reset a square",
+        "Right Click to create the goal",
+        "Middle Click to create the start",
+        "Press Q on two squares to link them with a portal",
+        "Press F on a square to make it one-way and rotate it",
+        "Press K to extend a patrol to a square, Left Shift + K to start one",
+        "Press J on two squares to add an agent going from one to the other",
+        "Press A for A* algorithm",
+        "Press D for Dijkstra's algorithm",
+        "Press G for Greedy Best first algorithm",
+        "Press I to search with HPA* and show its clusters and abstract graph",
+        "Press V to compare all algorithms side by side",
+        "Press S to plan around the patrols with space-time A*",
+        "Press X to plan every agent with CBS, Left Shift + X with Cooperative A*",
+        "Press L to send a crowd to the goal along a flow field",
+        "Press B to color the parts of the board that can reach each other",
+        "Press Y to measure every distance, then click a square to see its own",
+        "Press U to chase a wandering goal, Left Shift + U to chase it along the last patrol",
+        "Press C to clear the the board",
+        "Press M to generate a maze",
+        "Press Left Shift + M to watch the maze being generated",
+        "Press N to pick the next maze generator",
+        "Press O to pick where new maps place the start and goal",
+        "Press T to switch between square and hex grids",
+        "Press W to make the edges of the grid wrap around",
+        "Press R to generate the maze of the shown seed again",
+        "Press Left and Right to go through the solution",
+        "Press Space to play or pause the replay",
+        "Press Up and Down to change the replay speed",
+        "Press Home and End to jump to the first or last step",
+        "Drag the timeline to move through the replay",
+        "Hover a visited square to inspect its scores",
+        "Press P to show the parent of every square",
+        "Press E to color by expansion order, path cost or neither",
+        "Press H to hide the controls",
+    ];
+    let font_size = 26f32;
+    let x = 40f32;
+    let y = top_offset as f32 + 20f32;
+    // Split in columns when there are too many lines for the window
+    let lines_per_column = ((mq::screen_height() - y - 40f32) / font_size) as usize;
+    let column_width = controls
+        .iter()
+        .map(|line| mq::measure_text(line, None, font_size as u16, 1.0).width)
+        .fold(0f32, f32::max) + 30f32;
+    let columns = controls.len().div_ceil(lines_per_column);
+    let width = columns as f32 * column_width;
+    let height = (controls.len().min(lines_per_column) as f32 + 1.0) * font_size;
+    mq::draw_rectangle(x, y, width, height, mq::Color::new(1.0, 1.0, 1.0, 0.9));
+    mq::draw_rectangle_lines(x, y, width, height, 2f32, mq::BLACK);
+    for (i, line) in controls.iter().enumerate() {
+        let line_x = x + 15f32 + (i / lines_per_column) as f32 * column_width;
+        let line_y = y + ((i % lines_per_column) as f32 + 1.0) * font_size;
+        mq::draw_text(line, line_x, line_y, font_size, mq::BLACK);
+    }
 }
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::grid::{Cell, Direction, Grid};
+
+
+// Plain text maps, one line per grid row:
+// '#' is a wall, '.' is a blank square and the digits 2 to 9 are
+// weighted squares costing that much to enter. '^', '>', 'v' and '<' are
+// one-way squares, and any other lowercase letter is a portal linked to the
+// other square with the same letter.
+pub fn read_map(path: &str) -> Result<Grid, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read {path}: {e}"))?;
+    parse_map(&text)
+}
+
+
+pub fn parse_map(text: &str) -> Result<Grid, String> {
+    let rows: Vec<&str> = text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+    if rows.is_empty() {
+        return Err("the map is empty".to_string());
+    }
+    let width = rows[0].chars().count();
+    let mut grid = Grid::new(width, rows.len());
+    let mut portals: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(format!("row {} has {} squares, expected {width}", y + 1, row.chars().count()));
+        }
+        for (x, c) in row.chars().enumerate() {
+            let cell = match c {
+                '.' => Cell::Blank,
+                '#' => Cell::Wall,
+                '2'..='9' => Cell::Weighted(c.to_digit(10).unwrap() as u8),
+                '^' => Cell::OneWay(Direction::Up),
+                '>' => Cell::OneWay(Direction::Right),
+                'v' => Cell::OneWay(Direction::Down),
+                '<' => Cell::OneWay(Direction::Left),
+                'a'..='z' => {
+                    portals.entry(c).or_default().push((x, y));
+                    Cell::Blank
+                },
+                _ => return Err(format!("unknown square '{c}' at row {}, column {}", y + 1, x + 1))
+            };
+            grid.set_cell((x, y), cell);
+        }
+    }
+    let mut letters: Vec<char> = portals.keys().copied().collect();
+    letters.sort();
+    for letter in letters {
+        match portals[&letter].as_slice() {
+            [a, b] => grid.add_portal(*a, *b),
+            ends => return Err(format!("portal '{letter}' appears {} times, expected 2", ends.len()))
+        }
+    }
+    Ok(grid)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn reads_walls_and_weights() {
+        let grid = parse_map("#.5\n..#\n").unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get_cell((0, 0)), Cell::Wall);
+        assert_eq!(grid.get_cell((1, 0)), Cell::Blank);
+        assert_eq!(grid.get_cell((2, 0)), Cell::Weighted(5));
+        assert_eq!(grid.get_cell((2, 1)), Cell::Wall);
+    }
+
+
+    #[test]
+    fn rejects_bad_maps() {
+        assert!(parse_map("").is_err());
+        assert!(parse_map("\n\n").is_err());
+        assert!(parse_map("...\n..\n").is_err());
+        assert!(parse_map("..\n.@\n").is_err());
+        assert!(parse_map(".1\n..\n").is_err());
+    }
+
+
+    #[test]
+    fn links_portal_letters_in_pairs() {
+        let grid = parse_map("a.#b\n#..a\nb...\n").unwrap();
+        assert_eq!(grid.portal_exit((0, 0)), Some((3, 1)));
+        assert_eq!(grid.portal_exit((3, 1)), Some((0, 0)));
+        assert_eq!(grid.portal_exit((3, 0)), Some((0, 2)));
+        assert_eq!(grid.portal_exit((1, 1)), None);
+    }
+
+
+    #[test]
+    fn rejects_unpaired_portal_letters() {
+        assert!(parse_map("a..\n...\n").is_err());
+        assert!(parse_map("a.a\n.a.\n").is_err());
+        assert!(parse_map("a.b\n.a.\n").is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::time;
+
+use crate::grid::Grid;
+use crate::search::{self, Algorithm};
+use crate::space_time::{self, SpaceTimeResult};
+
+
+// Conflict-based search gives up after growing this many constraint tree nodes
+const MAX_CONSTRAINT_NODES: usize = 2000;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Solver {
+    Cooperative,
+    ConflictBased
+}
+
+
+impl Solver {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Solver::Cooperative => "Cooperative A*",
+            Solver::ConflictBased => "Conflict-based search"
+        }
+    }
+}
+
+
+// Why a solver stopped without a joint plan when there may be one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Limit {
+    // Conflict-based search grew MAX_CONSTRAINT_NODES nodes
+    ConstraintNodes,
+    // Some agent could only have been planned past max_time
+    TimeSteps
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Agent {
+    pub start: (usize, usize),
+    pub goal: (usize, usize),
+}
+
+
+pub struct MultiAgentResult {
+    pub solver: Solver,
+    // Square of every agent at every time step, until it reaches its goal
+    // for good. Empty if there is no joint plan.
+    pub paths: Vec<Vec<(usize, usize)>>,
+    // Added over every agent, waits included
+    pub cost: usize,
+    // Added over every space-time search
+    pub nodes_expanded: usize,
+    // Nodes grown in the constraint tree, 0 for cooperative A*
+    pub constraint_nodes: usize,
+    // Set when no joint plan was found because of a limit, not because
+    // there is none
+    pub gave_up: Option<Limit>,
+    // Last time step any agent can be planned at
+    pub max_time: usize,
+    pub duration: time::Duration,
+}
+
+
+impl MultiAgentResult {
+    pub fn found(&self) -> bool {
+        !self.paths.is_empty()
+    }
+
+
+    // Time step at which the last agent reaches its goal
+    pub fn makespan(&self) -> usize {
+        self.paths.iter().map(|path| path.len() - 1).max().unwrap_or(0)
+    }
+
+
+    pub fn position_at(&self, agent: usize, time: usize) -> (usize, usize) {
+        position_at(&self.paths[agent], time)
+    }
+}
+
+
+// Agents stay at their goal after their path ends
+fn position_at(path: &[(usize, usize)], time: usize) -> (usize, usize) {
+    path[time.min(path.len() - 1)]
+}
+
+
+// What one agent can't do in a node of the constraint tree
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Constraint {
+    // Be on the square at the time step
+    Vertex((usize, usize), usize),
+    // Move from the first square to the second one right after the time step
+    Edge((usize, usize), (usize, usize), usize),
+}
+
+
+struct ConstraintNode {
+    // Agent each constraint applies to
+    constraints: Vec<(usize, Constraint)>,
+    paths: Vec<Vec<(usize, usize)>>,
+    cost: usize,
+}
+
+
+// Plans every agent so no two are ever on the same square at the same time
+// step or swap squares with each other. Agents can wait in place, which
+// costs 1 like in a space-time search.
+pub fn solve(grid: &Grid, agents: &[Agent], solver: Solver) -> MultiAgentResult {
+    let time_start = time::Instant::now();
+    let mut result = MultiAgentResult {
+        solver,
+        paths: Vec::new(),
+        cost: 0,
+        nodes_expanded: 0,
+        constraint_nodes: 0,
+        gave_up: None,
+        max_time: 0,
+        duration: time::Duration::ZERO,
+    };
+    // Every agent has to reach its own goal alone, without sharing its start
+    // or its goal with another agent
+    let starts: HashSet<(usize, usize)> = agents.iter().map(|agent| agent.start).collect();
+    let goals: HashSet<(usize, usize)> = agents.iter().map(|agent| agent.goal).collect();
+    let mut lengths: Vec<usize> = Vec::new();
+    for agent in agents {
+        if !grid.is_walkable(agent.start) || !grid.is_walkable(agent.goal) { break }
+        let alone = search::search(grid, agent.start, agent.goal, Algorithm::AStar);
+        if !alone.found() { break }
+        lengths.push(alone.path_length());
+    }
+    if agents.is_empty() || starts.len() < agents.len() || goals.len() < agents.len() || lengths.len() < agents.len() {
+        result.duration = time_start.elapsed();
+        return result
+    }
+    // Enough for every agent to wait for all the others to walk to their
+    // goals one after the other. Not a bound on every plan, since an agent
+    // may have to step aside more than once, so searches that run into it
+    // are reported as giving up instead of as there being no plan.
+    let max_time = lengths.iter().sum::<usize>() + agents.len();
+    result.max_time = max_time;
+
+    match solver {
+        Solver::Cooperative => cooperative(grid, agents, max_time, &mut result),
+        Solver::ConflictBased => conflict_based(grid, agents, max_time, &mut result)
+    }
+    result.duration = time_start.elapsed();
+    result
+}
+
+
+// Plans the agents one after the other, each one avoiding the squares and
+// moves of the ones already planned
+// Silver, "Cooperative Pathfinding" (2005).
+fn cooperative(grid: &Grid, agents: &[Agent], max_time: usize, result: &mut MultiAgentResult) {
+    let mut paths: Vec<Vec<(usize, usize)>> = Vec::new();
+    let mut cost = 0;
+    for agent in agents {
+        let blocked = |from: (usize, usize), to: (usize, usize), time: usize| {
+            paths.iter().any(|path| {
+                let next = position_at(path, time + 1);
+                next == to || (position_at(path, time) == to && next == from)
+            })
+        };
+        // The goal must be free from the arrival on
+        let can_stay = |time: usize| paths.iter().all(|path| path.iter().skip(time).all(|sq| *sq != agent.goal));
+        let planned = space_time::search_with(grid, agent.start, agent.goal, max_time, blocked, can_stay, |_, _, _| 0);
+        result.nodes_expanded += planned.nodes_expanded;
+        if !planned.found() {
+            if planned.hit_max_time { result.gave_up = Some(Limit::TimeSteps) }
+            return
+        }
+        cost += planned.cost;
+        paths.push(planned.path);
+    }
+    result.paths = paths;
+    result.cost = cost;
+}
+
+
+// Plans every agent alone, then splits on the first collision into two
+// nodes, each forbidding one of the two agents from being part of it, and
+// keeps going from the cheapest node until one has no collisions. Ties go
+// to the node with the fewest collisions.
+// Sharon et al., "Conflict-based search for optimal multi-agent pathfinding" (2015).
+fn conflict_based(grid: &Grid, agents: &[Agent], max_time: usize, result: &mut MultiAgentResult) {
+    let mut nodes: Vec<ConstraintNode> = Vec::new();
+    // Entries are (cost, collisions, node), lowest first
+    let mut open_set: BinaryHeap<Reverse<(usize, usize, usize)>> = BinaryHeap::new();
+
+    let mut root = ConstraintNode { constraints: Vec::new(), paths: Vec::new(), cost: 0 };
+    for (i, agent) in agents.iter().enumerate() {
+        let planned = plan_constrained(grid, *agent, i, &root.constraints, &root.paths, max_time);
+        result.nodes_expanded += planned.nodes_expanded;
+        if !planned.found() {
+            if planned.hit_max_time { result.gave_up = Some(Limit::TimeSteps) }
+            return
+        }
+        root.cost += planned.cost;
+        root.paths.push(planned.path);
+    }
+    open_set.push(Reverse((root.cost, conflicts(&root.paths).len(), 0)));
+    nodes.push(root);
+    // Whether a node was dropped because its search ran into max_time
+    let mut hit_max_time = false;
+
+    while let Some(Reverse((_, _, index))) = open_set.pop() {
+        let conflict = match conflicts(&nodes[index].paths).first() {
+            Some(conflict) => *conflict,
+            None => {
+                let node = &mut nodes[index];
+                result.paths = std::mem::take(&mut node.paths);
+                result.cost = node.cost;
+                break
+            }
+        };
+        if nodes.len() >= MAX_CONSTRAINT_NODES {
+            result.gave_up = Some(Limit::ConstraintNodes);
+            break
+        }
+        for (agent, constraint) in conflict {
+            let mut constraints = nodes[index].constraints.clone();
+            constraints.push((agent, constraint));
+            let planned = plan_constrained(grid, agents[agent], agent, &constraints, &nodes[index].paths, max_time);
+            result.nodes_expanded += planned.nodes_expanded;
+            if !planned.found() {
+                hit_max_time |= planned.hit_max_time;
+                continue
+            }
+            let mut paths = nodes[index].paths.clone();
+            let old_cost = path_cost(grid, &paths[agent]);
+            paths[agent] = planned.path;
+            let cost = nodes[index].cost - old_cost + planned.cost;
+            open_set.push(Reverse((cost, conflicts(&paths).len(), nodes.len())));
+            nodes.push(ConstraintNode { constraints, paths, cost });
+        }
+    }
+    // Every node ran out without a plan, but some might have had one later
+    if hit_max_time && !result.found() && result.gave_up.is_none() {
+        result.gave_up = Some(Limit::TimeSteps)
+    }
+    result.constraint_nodes = nodes.len();
+}
+
+
+// Space-time search for one agent keeping to the constraints on it, and
+// running into the paths of the other agents as little as it can
+fn plan_constrained(grid: &Grid, agent: Agent, index: usize, constraints: &[(usize, Constraint)], paths: &[Vec<(usize, usize)>], max_time: usize) -> SpaceTimeResult {
+    let own: HashSet<Constraint> = constraints
+        .iter()
+        .filter(|(constrained, _)| *constrained == index)
+        .map(|(_, constraint)| *constraint)
+        .collect();
+    let blocked = |from: (usize, usize), to: (usize, usize), time: usize| {
+        own.contains(&Constraint::Vertex(to, time + 1)) || own.contains(&Constraint::Edge(from, to, time))
+    };
+    let can_stay = |time: usize| {
+        !own.iter().any(|constraint| matches!(constraint, Constraint::Vertex(sq, at) if *sq == agent.goal && *at >= time))
+    };
+    let collisions = |from: (usize, usize), to: (usize, usize), time: usize| {
+        paths
+            .iter()
+            .enumerate()
+            .filter(|(other, path)| {
+                let next = position_at(path, time + 1);
+                *other != index && (next == to || (position_at(path, time) == to && next == from))
+            })
+            .count()
+    };
+    space_time::search_with(grid, agent.start, agent.goal, max_time, blocked, can_stay, collisions)
+}
+
+
+// Same as the cost of a space-time search: waiting costs 1
+fn path_cost(grid: &Grid, path: &[(usize, usize)]) -> usize {
+    path.windows(2).map(|pair| if pair[0] == pair[1] { 1 } else { grid.step_cost(pair[0], pair[1]) }).sum()
+}
+
+
+// Every collision between two paths, earliest first, as the constraints
+// that would keep each of the two agents out of it
+fn conflicts(paths: &[Vec<(usize, usize)>]) -> Vec<[(usize, Constraint); 2]> {
+    let mut result: Vec<[(usize, Constraint); 2]> = Vec::new();
+    let makespan = paths.iter().map(|path| path.len()).max().unwrap_or(0);
+    for time in 0..makespan {
+        for a in 0..paths.len() {
+            for b in (a + 1)..paths.len() {
+                let (a_now, b_now) = (position_at(&paths[a], time), position_at(&paths[b], time));
+                if a_now == b_now {
+                    result.push([(a, Constraint::Vertex(a_now, time)), (b, Constraint::Vertex(b_now, time))]);
+                }
+                let (a_next, b_next) = (position_at(&paths[a], time + 1), position_at(&paths[b], time + 1));
+                if a_now == b_next && b_now == a_next && a_now != a_next {
+                    result.push([(a, Constraint::Edge(a_now, a_next, time)), (b, Constraint::Edge(b_now, b_next, time))]);
+                }
+            }
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_file;
+
+
+    // Every path starts and ends where it should and only moves to neighbors
+    // or waits, and no two agents share a square or swap squares
+    fn assert_valid(grid: &Grid, agents: &[Agent], result: &MultiAgentResult) {
+        assert!(result.found(), "{} found no plan", result.solver.name());
+        for (agent, path) in agents.iter().zip(&result.paths) {
+            assert_eq!(path.first(), Some(&agent.start));
+            assert_eq!(path.last(), Some(&agent.goal));
+            for pair in path.windows(2) {
+                assert!(pair[0] == pair[1] || grid.neighbors(pair[0]).contains(&pair[1]));
+            }
+        }
+        for time in 0..=result.makespan() {
+            for a in 0..agents.len() {
+                for b in (a + 1)..agents.len() {
+                    let (a_now, b_now) = (result.position_at(a, time), result.position_at(b, time));
+                    assert_ne!(a_now, b_now, "agents {a} and {b} meet at time {time}");
+                    let (a_next, b_next) = (result.position_at(a, time + 1), result.position_at(b, time + 1));
+                    assert!(!(a_now == b_next && b_now == a_next && a_now != a_next), "agents {a} and {b} swap at time {time}");
+                }
+            }
+        }
+    }
+
+
+    // Cooperative A* plans the first agent straight through, leaving the
+    // second one no time to reach the pocket
+    #[test]
+    fn conflict_based_search_swaps_agents_through_a_side_pocket() {
+        let grid = map_file::parse_map("##.##\n.....\n").unwrap();
+        let agents = [Agent { start: (0, 1), goal: (4, 1) }, Agent { start: (4, 1), goal: (0, 1) }];
+        assert_valid(&grid, &agents, &solve(&grid, &agents, Solver::ConflictBased));
+    }
+
+
+    #[test]
+    fn agents_crossing_an_open_room_never_collide() {
+        let grid = map_file::parse_map(".....\n.....\n.....\n.....\n.....\n").unwrap();
+        let agents = [
+            Agent { start: (0, 2), goal: (4, 2) },
+            Agent { start: (2, 0), goal: (2, 4) },
+            Agent { start: (4, 2), goal: (0, 2) },
+            Agent { start: (2, 4), goal: (2, 0) },
+        ];
+        for solver in [Solver::Cooperative, Solver::ConflictBased] {
+            assert_valid(&grid, &agents, &solve(&grid, &agents, solver));
+        }
+    }
+
+
+    #[test]
+    fn conflict_based_search_costs_no_more_than_cooperative() {
+        let grid = map_file::parse_map("##.##\n.....\n##.##\n").unwrap();
+        let agents = [Agent { start: (0, 1), goal: (4, 1) }, Agent { start: (2, 0), goal: (2, 2) }];
+        let cooperative = solve(&grid, &agents, Solver::Cooperative);
+        let conflict_based = solve(&grid, &agents, Solver::ConflictBased);
+        assert_valid(&grid, &agents, &cooperative);
+        assert_valid(&grid, &agents, &conflict_based);
+        assert!(conflict_based.cost <= cooperative.cost);
+    }
+}
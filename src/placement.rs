@@ -0,0 +1,168 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::grid::Grid;
+
+
+// Above this many squares the farthest pair is found with a double sweep
+// instead of a search from every square
+const EXACT_DIAMETER_LIMIT: usize = 400;
+
+
+// Where the start and goal go after a new map is generated
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Placement {
+    Manual,
+    Random,
+    Farthest,
+    Corners
+}
+
+
+impl Placement {
+    pub const ALL: [Placement; 4] = [Placement::Manual, Placement::Random, Placement::Farthest, Placement::Corners];
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placement::Manual => "Manual",
+            Placement::Random => "Random",
+            Placement::Farthest => "Farthest apart",
+            Placement::Corners => "Corners"
+        }
+    }
+
+
+    // Name given to --placement in the settings
+    pub fn id(&self) -> &'static str {
+        match self {
+            Placement::Manual => "manual",
+            Placement::Random => "random",
+            Placement::Farthest => "farthest",
+            Placement::Corners => "corners"
+        }
+    }
+
+
+    pub fn from_id(id: &str) -> Option<Placement> {
+        Placement::ALL.into_iter().find(|placement| placement.id() == id)
+    }
+
+
+    // Cycled through with the O key
+    pub fn next(&self) -> Placement {
+        let index = Placement::ALL.iter().position(|placement| placement == self).unwrap();
+        Placement::ALL[(index + 1) % Placement::ALL.len()]
+    }
+}
+
+
+// Start and goal for the given placement, both in the largest connected
+// part of the grid so they can reach each other. None when the placement
+// is manual or there are not two squares to choose from.
+// The same grid, placement and seed always give the same squares.
+pub fn choose_endpoints(grid: &Grid, placement: Placement, seed: u64) -> Option<((usize, usize), (usize, usize))> {
+    let components = grid.strong_components();
+    let largest = components.first()?;
+    if largest.len() < 2 { return None }
+    match placement {
+        Placement::Manual => None,
+        Placement::Random => {
+            let rng = &mut StdRng::seed_from_u64(seed);
+            let chosen: Vec<&(usize, usize)> = largest.choose_multiple(rng, 2).collect();
+            Some((*chosen[0], *chosen[1]))
+        },
+        Placement::Farthest => Some(farthest_pair(grid, largest)),
+        Placement::Corners => {
+            let start = nearest(grid, largest, (0, 0), None);
+            let goal = nearest(grid, largest, (grid.width() - 1, grid.height() - 1), Some(start));
+            Some((start, goal))
+        }
+    }
+}
+
+
+// Closest on the board as drawn, so a wrapped grid doesn't count the
+// opposite corner as near
+fn nearest(grid: &Grid, squares: &[(usize, usize)], target: (usize, usize), taken: Option<(usize, usize)>) -> (usize, usize) {
+    *squares
+        .iter()
+        .filter(|sq| Some(**sq) != taken)
+        .min_by_key(|sq| grid.manhattan_distance(**sq, target))
+        .unwrap()
+}
+
+
+// Path cost from the source to every square it can reach
+fn distances(grid: &Grid, source: (usize, usize)) -> HashMap<(usize, usize), usize> {
+    let mut result: HashMap<(usize, usize), usize> = HashMap::from([(source, 0)]);
+    let mut open_set = BinaryHeap::from([Reverse((0, source))]);
+    while let Some(Reverse((cost, current))) = open_set.pop() {
+        if cost > result[&current] { continue }
+        for next in grid.neighbors(current) {
+            let next_cost = cost + grid.step_cost(current, next);
+            if result.get(&next).is_none_or(|old| next_cost < *old) {
+                result.insert(next, next_cost);
+                open_set.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    result
+}
+
+
+fn farthest_from(grid: &Grid, source: (usize, usize)) -> ((usize, usize), usize) {
+    distances(grid, source)
+        .into_iter()
+        .max_by_key(|(sq, cost)| (*cost, Reverse(*sq)))
+        .unwrap()
+}
+
+
+// The two squares with the most expensive shortest path between them.
+// Small components search from every square; big ones search from the
+// square farthest from an arbitrary one, which is exact on perfect mazes.
+// https://en.wikipedia.org/wiki/Distance_(graph_theory)
+fn farthest_pair(grid: &Grid, component: &[(usize, usize)]) -> ((usize, usize), (usize, usize)) {
+    if component.len() > EXACT_DIAMETER_LIMIT {
+        let (start, _) = farthest_from(grid, component[0]);
+        let (goal, _) = farthest_from(grid, start);
+        return (start, goal)
+    }
+    let mut best = (component[0], component[0], 0);
+    for &start in component {
+        let (goal, cost) = farthest_from(grid, start);
+        if cost > best.2 {
+            best = (start, goal, cost);
+        }
+    }
+    (best.0, best.1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Cell;
+
+
+    #[test]
+    fn corners_ignore_wrapping() {
+        for wrap in [false, true] {
+            // The top left corner is walled in, while the other three corners
+            // are one move away from it across the edges when wrapping
+            let mut grid = Grid::new(8, 6);
+            grid.set_wrap(wrap);
+            for wall in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                grid.set_cell(wall, Cell::Wall);
+            }
+            let (start, goal) = choose_endpoints(&grid, Placement::Corners, 0).unwrap();
+            assert_eq!(grid.manhattan_distance(start, (0, 0)), 2, "start {start:?} with wrap {wrap}");
+            assert_eq!(goal, (7, 5));
+        }
+    }
+}
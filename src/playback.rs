@@ -0,0 +1,128 @@
+use macroquad::prelude as mq;
+use macroquad::input as input_mq;
+
+use crate::squares::SquareCollection;
+
+
+// Replay states shown per second
+const SPEEDS: [f32; 9] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0];
+
+
+// Automatic stepping through the replay, plus the timeline bar at the
+// bottom of the top panel
+pub struct Playback {
+    top_offset: usize,
+    playing: bool,
+    speed_index: usize,
+    // Part of a step already played but not shown yet
+    pending: f32,
+    dragging: bool,
+}
+
+
+impl Playback {
+    pub fn new(top_offset: usize) -> Playback {
+        Playback {
+            top_offset,
+            playing: false,
+            speed_index: 4,
+            pending: 0.0,
+            dragging: false,
+        }
+    }
+
+
+    // Starts over when pressing play at the last state
+    pub fn toggle(&mut self, square_collection: &mut SquareCollection) {
+        let (step, total_steps) = square_collection.get_replay_position();
+        if !self.playing && step == total_steps {
+            square_collection.set_replay_state(0);
+        }
+        self.playing = !self.playing;
+        self.pending = 0.0;
+    }
+
+
+    // Plays a new replay from its first state, whether or not one was playing
+    pub fn play(&mut self, square_collection: &mut SquareCollection) {
+        square_collection.set_replay_state(0);
+        self.playing = true;
+        self.pending = 0.0;
+    }
+
+
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(SPEEDS.len() - 1);
+    }
+
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+
+    pub fn update(&mut self, square_collection: &mut SquareCollection) {
+        let (step, total_steps) = square_collection.get_replay_position();
+        if total_steps == 0 {
+            self.playing = false;
+            return
+        }
+
+        // Timeline
+        let timeline = self.get_timeline_rect();
+        let mouse = input_mq::mouse_position();
+        if input_mq::is_mouse_button_pressed(mq::MouseButton::Left) && timeline.contains(mq::vec2(mouse.0, mouse.1)) {
+            self.dragging = true;
+        }
+        if !input_mq::is_mouse_button_down(mq::MouseButton::Left) {
+            self.dragging = false;
+        }
+        if self.dragging {
+            self.playing = false;
+            let fraction = ((mouse.0 - timeline.x) / timeline.w).clamp(0.0, 1.0);
+            square_collection.set_replay_state((fraction * (total_steps - 1) as f32).round() as usize);
+            return
+        }
+
+        // Automatic stepping, stopping at the last state
+        if self.playing {
+            self.pending += mq::get_frame_time() * SPEEDS[self.speed_index];
+            let steps = self.pending as usize;
+            self.pending -= steps as f32;
+            if steps > 0 {
+                square_collection.set_replay_state(step - 1 + steps);
+            }
+            if square_collection.get_replay_position().0 == total_steps {
+                self.playing = false;
+            }
+        }
+    }
+
+
+    fn get_timeline_rect(&self) -> mq::Rect {
+        let x = 250f32;
+        mq::Rect::new(x, self.top_offset as f32 - 30f32, mq::screen_width() - x - 20f32, 18f32)
+    }
+
+
+    pub fn draw(&self, square_collection: &SquareCollection) {
+        let (step, total_steps) = square_collection.get_replay_position();
+        let timeline = self.get_timeline_rect();
+        let state = if self.playing { "Playing" } else { "Paused" };
+        let label = format!("{state} at {}/s", SPEEDS[self.speed_index]);
+        mq::draw_text(&label, 5f32, timeline.y + timeline.h, 26f32, mq::BLACK);
+
+        mq::draw_rectangle(timeline.x, timeline.y, timeline.w, timeline.h, mq::LIGHTGRAY);
+        if total_steps > 0 {
+            let fraction = if total_steps > 1 { (step - 1) as f32 / (total_steps - 1) as f32 } else { 1.0 };
+            mq::draw_rectangle(timeline.x, timeline.y, timeline.w * fraction, timeline.h, mq::SKYBLUE);
+            mq::draw_rectangle(timeline.x + timeline.w * fraction - 4f32, timeline.y - 4f32, 8f32, timeline.h + 8f32, mq::DARKBLUE);
+        }
+        mq::draw_rectangle_lines(timeline.x, timeline.y, timeline.w, timeline.h, 2f32, mq::BLACK);
+    }
+}
@@ -0,0 +1,112 @@
+use std::time;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::grid::Grid;
+use crate::search::{self, Algorithm};
+use crate::space_time::Patrol;
+
+
+// How the target moves at every tick
+pub enum TargetMotion {
+    // To a random neighbor, the same seed giving the same walk
+    Wander { start: (usize, usize), seed: u64 },
+    // Back and forth along a route, from its first square
+    Route(Patrol),
+}
+
+
+pub struct PursuitResult {
+    pub algorithm: Algorithm,
+    // Squares of the agent and the target at every tick, from where they start
+    pub agent: Vec<(usize, usize)>,
+    pub target: Vec<(usize, usize)>,
+    // Path the agent is following at every tick, from its square to the
+    // square where it last saw the target. Empty when it can't reach it.
+    pub plans: Vec<Vec<(usize, usize)>>,
+    // Ticks at which the agent searched again
+    pub replans: Vec<usize>,
+    // Added over every search
+    pub nodes_expanded: usize,
+    // Of the squares the agent walked
+    pub cost: usize,
+    pub caught: bool,
+    pub duration: time::Duration,
+}
+
+
+impl PursuitResult {
+    pub fn ticks(&self) -> usize {
+        self.agent.len() - 1
+    }
+}
+
+
+// The agent takes one step of its path every tick, then the target moves.
+// A target stepping onto the rest of the path only shortens it, since part
+// of a shortest path is a shortest path too. Anywhere else the agent
+// searches again. It gives up after max_ticks.
+pub fn pursue(grid: &Grid, start: (usize, usize), motion: &TargetMotion, algorithm: Algorithm, max_ticks: usize) -> PursuitResult {
+    let time_start = time::Instant::now();
+    let (mut target, seed) = match motion {
+        TargetMotion::Wander { start, seed } => (*start, *seed),
+        TargetMotion::Route(patrol) => (patrol.position_at(0), 0)
+    };
+    let rng = &mut StdRng::seed_from_u64(seed);
+
+    let mut agent = start;
+    let mut agents: Vec<(usize, usize)> = Vec::new();
+    let mut targets: Vec<(usize, usize)> = Vec::new();
+    let mut plans: Vec<Vec<(usize, usize)>> = Vec::new();
+    let mut replans: Vec<usize> = Vec::new();
+    let mut nodes_expanded = 0;
+    let mut cost = 0;
+    let mut plan: Vec<(usize, usize)> = Vec::new();
+    // Target square the plan leads to
+    let mut planned_for: Option<(usize, usize)> = None;
+    loop {
+        let tick = agents.len();
+        let caught = agent == target;
+        if let Some(index) = plan.iter().position(|sq| *sq == target) {
+            plan.truncate(index + 1);
+            planned_for = Some(target);
+        }
+        if !caught && tick < max_ticks && planned_for != Some(target) {
+            let result = search::search(grid, agent, target, algorithm);
+            nodes_expanded += result.nodes_expanded();
+            replans.push(tick);
+            plan = result.path;
+            planned_for = Some(target);
+        }
+        agents.push(agent);
+        targets.push(target);
+        plans.push(plan.clone());
+        if caught || tick == max_ticks { break }
+
+        if plan.len() > 1 {
+            cost += grid.step_cost(plan[0], plan[1]);
+            plan.remove(0);
+            agent = plan[0];
+        }
+        // Walking into the target catches it before it can move
+        if agent == target { continue }
+        target = match motion {
+            TargetMotion::Wander { .. } => *grid.neighbors(target).choose(rng).unwrap_or(&target),
+            TargetMotion::Route(patrol) => patrol.position_at(tick + 1)
+        };
+    }
+
+    PursuitResult {
+        algorithm,
+        caught: agent == target,
+        agent: agents,
+        target: targets,
+        plans,
+        replans,
+        nodes_expanded,
+        cost,
+        duration: time_start.elapsed(),
+    }
+}
@@ -0,0 +1,289 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::time;
+
+use crate::grid::Grid;
+
+
+// Entries are (score, tie breaker, square), lowest first
+type OpenSet = BinaryHeap<Reverse<(usize, usize, (usize, usize))>>;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Algorithm {
+    AStar,
+    Dijkstra,
+    GreedyBestFirst
+}
+
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 3] = [Algorithm::AStar, Algorithm::Dijkstra, Algorithm::GreedyBestFirst];
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::AStar => "A*",
+            Algorithm::Dijkstra => "Dijkstra",
+            Algorithm::GreedyBestFirst => "Greedy Best first"
+        }
+    }
+
+
+    // Short name used on the command line and in machine readable output
+    pub fn id(&self) -> &'static str {
+        match self {
+            Algorithm::AStar => "astar",
+            Algorithm::Dijkstra => "dijkstra",
+            Algorithm::GreedyBestFirst => "greedy"
+        }
+    }
+
+
+    pub fn from_id(id: &str) -> Option<Algorithm> {
+        Algorithm::ALL.into_iter().find(|algorithm| algorithm.id() == id)
+    }
+
+
+    // true true  -> A*
+    // true false -> Dijkstra
+    // false true -> Greedy Best first
+    fn weights(&self) -> bool {
+        *self != Algorithm::GreedyBestFirst
+    }
+
+
+    fn heuristics(&self) -> bool {
+        *self != Algorithm::Dijkstra
+    }
+}
+
+
+// One expansion of the main loop: the node taken out of the open set
+// and the nodes that were added to the open set because of it
+#[derive(Clone)]
+pub struct SearchStep {
+    pub current: (usize, usize),
+    pub opened: Vec<(usize, usize)>,
+}
+
+
+#[derive(Clone)]
+pub struct SearchResult {
+    pub algorithm: Algorithm,
+    // From start to objective, both included. Empty if there is no path.
+    pub path: Vec<(usize, usize)>,
+    pub cost: usize,
+    pub steps: Vec<SearchStep>,
+    // Largest number of nodes waiting in the open set at once
+    pub max_open_set: usize,
+    pub duration: time::Duration,
+    // Final values of every discovered node, kept for inspection
+    pub came_from: HashMap<(usize, usize), (usize, usize)>,
+    pub path_costs: HashMap<(usize, usize), usize>,
+    pub heuristic_values: HashMap<(usize, usize), usize>,
+    pub scores: HashMap<(usize, usize), usize>,
+    pub expanded_at: HashMap<(usize, usize), usize>,
+}
+
+
+impl SearchResult {
+    pub fn found(&self) -> bool {
+        !self.path.is_empty()
+    }
+
+
+    pub fn nodes_expanded(&self) -> usize {
+        self.steps.len()
+    }
+
+
+    // Number of moves in the path
+    pub fn path_length(&self) -> usize {
+        self.path.len().saturating_sub(1)
+    }
+
+
+    // Whether the algorithm adds the heuristic to the score
+    pub fn uses_heuristics(&self) -> bool {
+        self.algorithm.heuristics()
+    }
+
+
+    pub fn outcome(&self) -> Outcome {
+        if self.found() {
+            Outcome::Found { path: self.path.clone(), cost: self.cost }
+        } else {
+            Outcome::Unreachable
+        }
+    }
+}
+
+
+// What came of asking for a path, whether or not a search could run
+#[derive(Clone, PartialEq, Debug)]
+pub enum Outcome {
+    // From start to objective, both included
+    Found { path: Vec<(usize, usize)>, cost: usize },
+    // The open set ran out, or would have, before reaching the objective
+    Unreachable,
+    // Not placed, outside the grid or a wall
+    MissingStart,
+    MissingObjective
+}
+
+
+impl Outcome {
+    // Short name used in machine readable output
+    pub fn id(&self) -> &'static str {
+        match self {
+            Outcome::Found { .. } => "found",
+            Outcome::Unreachable => "unreachable",
+            Outcome::MissingStart => "missing_start",
+            Outcome::MissingObjective => "missing_goal"
+        }
+    }
+
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Outcome::Found { .. } => "Path found",
+            Outcome::Unreachable => "No path: the goal can't be reached from the start",
+            Outcome::MissingStart => "No start to search from",
+            Outcome::MissingObjective => "No goal to search for"
+        }
+    }
+}
+
+
+// Start and objective
+type Endpoints = ((usize, usize), (usize, usize));
+
+
+// The start and objective when a search can run between them, otherwise
+// which one is missing
+pub fn check_endpoints(grid: &Grid, start: Option<(usize, usize)>, objective: Option<(usize, usize)>) -> Result<Endpoints, Outcome> {
+    let usable = |pos: &(usize, usize)| grid.contains(*pos) && grid.is_walkable(*pos);
+    let start = start.filter(usable).ok_or(Outcome::MissingStart)?;
+    let objective = objective.filter(usable).ok_or(Outcome::MissingObjective)?;
+    Ok((start, objective))
+}
+
+
+// https://en.wikipedia.org/wiki/A*_search_algorithm
+pub fn search(grid: &Grid, start: (usize, usize), objective: (usize, usize), algorithm: Algorithm) -> SearchResult {
+    let start_time = time::Instant::now();
+    let weights = algorithm.weights();
+    let heuristics = algorithm.heuristics();
+
+    let mut open_set: OpenSet = BinaryHeap::new();
+    let mut closed_set: HashSet<(usize, usize)> = HashSet::new();
+    let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    let mut path_costs: HashMap<(usize, usize), usize> = HashMap::new(); // gScore
+    let mut scores: HashMap<(usize, usize), usize> = HashMap::new(); // fScore
+    let mut heuristic_values: HashMap<(usize, usize), usize> = HashMap::new(); // hScore
+    let mut steps: Vec<SearchStep> = Vec::new();
+    let mut max_open_set = 1;
+
+    // Ties are broken by the distance to the objective when using heuristics
+    let tie_breaker = |pos: (usize, usize)| if heuristics { grid.heuristic(pos, objective) } else { 0 };
+
+    path_costs.insert(start, 0);
+    heuristic_values.insert(start, grid.heuristic(start, objective));
+    scores.insert(start, if heuristics { heuristic_values[&start] } else { 0 });
+    open_set.push(Reverse((scores[&start], tie_breaker(start), start)));
+
+    let mut path: Vec<(usize, usize)> = Vec::new();
+    // Stale entries, left behind when a node gets a better score, are skipped
+    while let Some(Reverse((score, _, current))) = open_set.pop() {
+        if closed_set.contains(&current) || scores[&current] != score { continue }
+        if current == objective {
+            path.push(current);
+            let mut previous = current;
+            while previous != start {
+                previous = came_from[&previous];
+                path.push(previous);
+            }
+            path.reverse();
+            break;
+        }
+
+        closed_set.insert(current);
+        let mut opened: Vec<(usize, usize)> = Vec::new();
+        for neighbor in grid.neighbors(current) {
+            if closed_set.contains(&neighbor) { continue }
+            let mut tentative_path_cost = path_costs[&current];
+            if weights {
+                tentative_path_cost += grid.step_cost(current, neighbor);
+            }
+            let discovered = path_costs.contains_key(&neighbor);
+            if !discovered || tentative_path_cost < path_costs[&neighbor] {
+                came_from.insert(neighbor, current);
+                path_costs.insert(neighbor, tentative_path_cost);
+                let heuristic = *heuristic_values.entry(neighbor).or_insert_with(|| grid.heuristic(neighbor, objective));
+                if heuristics {
+                    tentative_path_cost += heuristic;
+                }
+                scores.insert(neighbor, tentative_path_cost);
+                open_set.push(Reverse((tentative_path_cost, tie_breaker(neighbor), neighbor)));
+                if !discovered {
+                    opened.push(neighbor);
+                }
+            }
+        }
+        steps.push(SearchStep { current, opened });
+        // Every discovered node that is not closed yet is in the open set
+        max_open_set = max_open_set.max(path_costs.len() - closed_set.len());
+    }
+
+    let cost = path.windows(2).map(|pair| grid.step_cost(pair[0], pair[1])).sum();
+    let duration = start_time.elapsed();
+    let expanded_at = steps.iter().enumerate().map(|(i, step)| (step.current, i)).collect();
+    SearchResult {
+        algorithm,
+        path,
+        cost,
+        steps,
+        max_open_set,
+        duration,
+        came_from,
+        path_costs,
+        heuristic_values,
+        scores,
+        expanded_at,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Cell;
+
+
+    // Same as the headless runner: search only when both endpoints are usable
+    fn outcome(grid: &Grid, start: Option<(usize, usize)>, objective: Option<(usize, usize)>) -> Outcome {
+        match check_endpoints(grid, start, objective) {
+            Ok((start, objective)) => search(grid, start, objective, Algorithm::AStar).outcome(),
+            Err(outcome) => outcome
+        }
+    }
+
+
+    #[test]
+    fn every_outcome_is_reported() {
+        // The right column is walled off from the rest
+        let mut grid = Grid::new(4, 3);
+        for y in 0..3 {
+            grid.set_cell((2, y), Cell::Wall);
+        }
+        assert_eq!(outcome(&grid, Some((0, 0)), Some((1, 2))), Outcome::Found { path: vec![(0, 0), (0, 1), (0, 2), (1, 2)], cost: 3 });
+        assert_eq!(outcome(&grid, Some((0, 0)), Some((3, 0))), Outcome::Unreachable);
+        assert_eq!(outcome(&grid, None, Some((0, 0))), Outcome::MissingStart);
+        assert_eq!(outcome(&grid, Some((2, 1)), Some((0, 0))), Outcome::MissingStart);
+        assert_eq!(outcome(&grid, Some((9, 9)), Some((0, 0))), Outcome::MissingStart);
+        assert_eq!(outcome(&grid, Some((0, 0)), None), Outcome::MissingObjective);
+        assert_eq!(outcome(&grid, Some((0, 0)), Some((2, 0))), Outcome::MissingObjective);
+        assert_eq!(outcome(&grid, None, None), Outcome::MissingStart);
+    }
+}
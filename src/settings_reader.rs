@@ -1,13 +1,48 @@
 use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+
+
+static SETTINGS: OnceLock<HashMap<String, String>> = OnceLock::new();
 
 
 // Modify the settings in the extra_folder folder, nothing to see here.
-pub fn get_settings() -> HashMap<String, String> {
-    HashMap::<&str, &str>::from([
+// Any of them can be overridden from the command line with --name value.
+// The arguments are only read the first time, so unknown ones are only
+// reported once.
+pub fn get_settings() -> &'static HashMap<String, String> {
+    SETTINGS.get_or_init(read_settings)
+}
+
+
+fn read_settings() -> HashMap<String, String> {
+    let mut settings: HashMap<String, String> = HashMap::<&str, &str>::from([
         ("window_width", "1600"),
-        ("window_height", "1000"),
+        ("window_height", "1065"),
         ("window_title", "Pathfinding"),
         ("square_width", "50"),
-        ("top_offset", "100")
-    ]).into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        ("top_offset", "165"),
+        ("generator", "backtracker"),
+        ("seed", "random"),
+        ("density", "0.45"),
+        ("iterations", "4"),
+        ("braid", "0"),
+        ("placement", "manual"),
+        ("topology", "square"),
+        ("wrap", "false"),
+        ("portal_cost", "1"),
+        ("flow_units", "60"),
+        ("cluster_size", "8")
+    ]).into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        match pair {
+            [name, value] if name.starts_with("--") && settings.contains_key(&name[2..]) => {
+                settings.insert(name[2..].to_string(), value.to_string());
+            },
+            _ => eprintln!("Ignoring unknown argument {}", pair.join(" "))
+        }
+    }
+    settings
 }
@@ -0,0 +1,193 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::time;
+
+use crate::grid::Grid;
+
+
+// Obstacle walking its route back and forth, one square per time step
+#[derive(Clone)]
+pub struct Patrol {
+    // Adjacent squares, from the first waypoint to the last one
+    pub route: Vec<(usize, usize)>,
+}
+
+
+impl Patrol {
+    pub fn position_at(&self, time: usize) -> (usize, usize) {
+        if self.route.len() < 2 {
+            return self.route[0]
+        }
+        let period = 2 * (self.route.len() - 1);
+        let index = time % period;
+        if index < self.route.len() { self.route[index] } else { self.route[period - index] }
+    }
+}
+
+
+pub struct SpaceTimeResult {
+    // Square of the agent at every time step, starting at the start at time 0.
+    // Empty if the goal can't be reached in time.
+    pub path: Vec<(usize, usize)>,
+    pub cost: usize,
+    // Time steps spent standing still
+    pub waits: usize,
+    // Whether some state was left unexpanded at max_time, so a later path
+    // may have been missed
+    pub hit_max_time: bool,
+    // Last time step the search looked at
+    pub max_time: usize,
+    pub nodes_expanded: usize,
+    pub duration: time::Duration,
+}
+
+
+impl SpaceTimeResult {
+    pub fn found(&self) -> bool {
+        !self.path.is_empty()
+    }
+}
+
+
+// A square and a time step
+type State = ((usize, usize), usize);
+
+
+// Entries are (score, collisions, heuristic, time step, square), lowest first
+type OpenSet = BinaryHeap<Reverse<(usize, usize, usize, usize, (usize, usize))>>;
+
+
+// Plans around the patrols. A move is not allowed onto a square an obstacle
+// will be on, nor through an obstacle coming the other way.
+pub fn search(grid: &Grid, start: (usize, usize), objective: (usize, usize), patrols: &[Patrol], max_time: usize) -> SpaceTimeResult {
+    let blocked = |from: (usize, usize), to: (usize, usize), time: usize| {
+        patrols.iter().any(|patrol| {
+            let next = patrol.position_at(time + 1);
+            next == to || (patrol.position_at(time) == to && next == from)
+        })
+    };
+    search_with(grid, start, objective, max_time, blocked, |_| true, |_, _, _| 0)
+}
+
+
+// A* over squares and time, moving to a neighbor or waiting in place at every
+// step. blocked(from, to, time) forbids the move from time to time + 1 and
+// the path can only end at the objective at the times can_stay allows.
+// Between paths of the same cost, the one with the fewest collisions(from,
+// to, time) is taken. Waiting costs 1 and the search gives up after
+// max_time steps.
+// Space-time A* from Silver, "Cooperative Pathfinding" (2005).
+pub fn search_with(grid: &Grid, start: (usize, usize), objective: (usize, usize), max_time: usize, blocked: impl Fn((usize, usize), (usize, usize), usize) -> bool, can_stay: impl Fn(usize) -> bool, collisions: impl Fn((usize, usize), (usize, usize), usize) -> usize) -> SpaceTimeResult {
+    let time_start = time::Instant::now();
+    // Waiting is always possible, so an objective that can't be reached at all
+    // would only stop the search at max_time
+    if !grid.can_reach(start, objective) {
+        return SpaceTimeResult { path: Vec::new(), cost: 0, waits: 0, hit_max_time: false, max_time, nodes_expanded: 0, duration: time_start.elapsed() }
+    }
+
+    let mut open_set: OpenSet = BinaryHeap::new();
+    let mut closed_set: HashSet<State> = HashSet::new();
+    let mut came_from: HashMap<State, State> = HashMap::new();
+    let mut path_costs: HashMap<State, usize> = HashMap::from([((start, 0), 0)]);
+    let mut path_collisions: HashMap<State, usize> = HashMap::from([((start, 0), 0)]);
+    let heuristic = grid.heuristic(start, objective);
+    open_set.push(Reverse((heuristic, 0, heuristic, 0, start)));
+
+    let mut path: Vec<(usize, usize)> = Vec::new();
+    let mut cost = 0;
+    let mut hit_max_time = false;
+    while let Some(Reverse((_, _, _, time, current))) = open_set.pop() {
+        if !closed_set.insert((current, time)) { continue }
+        if current == objective && can_stay(time) {
+            cost = path_costs[&(current, time)];
+            let mut state = (current, time);
+            path.push(current);
+            while let Some(previous) = came_from.get(&state) {
+                path.push(previous.0);
+                state = *previous;
+            }
+            path.reverse();
+            break;
+        }
+        if time == max_time {
+            hit_max_time = true;
+            continue
+        }
+
+        let mut moves: Vec<((usize, usize), usize)> = grid
+            .neighbors(current)
+            .into_iter()
+            .map(|neighbor| (neighbor, grid.step_cost(current, neighbor)))
+            .collect();
+        moves.push((current, 1));
+        for (next, step_cost) in moves {
+            let state = (next, time + 1);
+            if closed_set.contains(&state) || blocked(current, next, time) { continue }
+            let path_cost = path_costs[&(current, time)] + step_cost;
+            let collisions = path_collisions[&(current, time)] + collisions(current, next, time);
+            let better = match path_costs.get(&state) {
+                Some(old) => path_cost < *old || (path_cost == *old && collisions < path_collisions[&state]),
+                None => true
+            };
+            if better {
+                came_from.insert(state, (current, time));
+                path_costs.insert(state, path_cost);
+                path_collisions.insert(state, collisions);
+                let heuristic = grid.heuristic(next, objective);
+                open_set.push(Reverse((path_cost + heuristic, collisions, heuristic, time + 1, next)));
+            }
+        }
+    }
+
+    let waits = path.windows(2).filter(|pair| pair[0] == pair[1]).count();
+    SpaceTimeResult {
+        path,
+        cost,
+        waits,
+        hit_max_time,
+        max_time,
+        nodes_expanded: closed_set.len(),
+        duration: time_start.elapsed(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_file;
+
+
+    // A corridor crossed by a patrol that is in it at every odd time step
+    fn crossing() -> (Grid, Vec<Patrol>) {
+        let grid = map_file::parse_map("##.##\n.....\n##.##\n").unwrap();
+        (grid, vec![Patrol { route: vec![(2, 0), (2, 1), (2, 2)] }])
+    }
+
+
+    #[test]
+    fn waits_for_a_patrol_to_pass() {
+        let (grid, patrols) = crossing();
+        let result = search(&grid, (1, 1), (4, 1), &patrols, 50);
+        assert!(result.found());
+        assert_eq!(result.cost, 4);
+        assert!(result.waits >= 1);
+        for (time, sq) in result.path.iter().enumerate() {
+            assert_ne!(*sq, patrols[0].position_at(time), "met the patrol at time step {time}");
+        }
+    }
+
+
+    #[test]
+    fn tells_running_out_of_time_from_no_path() {
+        let (grid, patrols) = crossing();
+        let result = search(&grid, (1, 1), (4, 1), &patrols, 3);
+        assert!(!result.found());
+        assert!(result.hit_max_time);
+        assert_eq!(result.max_time, 3);
+
+        let result = search(&grid, (0, 1), (0, 0), &patrols, 50);
+        assert!(!result.found());
+        assert!(!result.hit_max_time);
+    }
+}
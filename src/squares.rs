@@ -1,6 +1,16 @@
 use macroquad::prelude as mq;
-use std::collections::{HashMap, HashSet};
-use rand::Rng;
+use std::collections::HashMap;
+
+use pathfinding::all_pairs::{self, DistanceTable, Method};
+use pathfinding::flow_field::{self, FlowField};
+use pathfinding::generators::{self, Generator, GeneratorOptions};
+use pathfinding::grid::{Cell, Direction, Grid, Topology, MAX_COST};
+use pathfinding::hierarchical::{HierarchicalResult, Hierarchy};
+use pathfinding::multi_agent::{self, Agent, MultiAgentResult, Solver};
+use pathfinding::placement::{self, Placement};
+use pathfinding::pursuit::{self, PursuitResult, TargetMotion};
+use pathfinding::search::{self, Algorithm, Outcome, SearchResult};
+use pathfinding::space_time::{self, Patrol, SpaceTimeResult};
 
 
 #[derive(Clone)]
@@ -10,8 +20,11 @@ pub enum SquareType {
      Objective,
      Start,
      Blank,
+     Open,
      Visited,
-     Solution
+     Solution,
+     // Linked to the other portal with the same number
+     Portal(usize)
  }
 
 
@@ -21,21 +34,34 @@ pub struct Square {
     x_grid: usize,
     y_grid: usize,
     square_type: SquareType,
+    // Terrain cost of entering the square, kept while its type changes
+    cost: u8,
+    // Way a one-way square lets through, kept while its type changes too
+    direction: Option<Direction>,
 }
 
 
+// Every square of the board, indexed by x then y
+type Board = Vec<Vec<Square>>;
+
+
 impl Square {
     pub fn new(x: usize, y: usize) -> Square {
         Square {
             x_grid: x,
             y_grid: y,
-            square_type: SquareType::Blank
+            square_type: SquareType::Blank,
+            cost: 1,
+            direction: None
         }
     }
 
 
+    // The start and the portals stay visible under the marks of a search
     pub fn set_square_type(&mut self, square_type: SquareType) {
-        if !(self.square_type == SquareType::Start && square_type == SquareType::Visited) {
+        let kept = matches!(self.square_type, SquareType::Start | SquareType::Portal(_));
+        let search_mark = matches!(square_type, SquareType::Visited | SquareType::Open | SquareType::Solution);
+        if !(kept && search_mark) {
             self.square_type = square_type;
         }
     }
@@ -46,18 +72,185 @@ impl Square {
     }
 
 
-    fn draw(&self, square_width: usize, top_offset: usize) {
-        let x_coord = self.x_grid * square_width;
-        let y_coord = self.y_grid * square_width + top_offset;
-        let color = match self.square_type {
-            SquareType::Blank => mq::WHITE,
-            SquareType::Wall=> mq::BLACK,
-            SquareType::Start=> mq::GREEN,
-            SquareType::Objective => mq::RED,
-            SquareType::Visited => mq::BLUE,
-            SquareType::Solution => mq::BROWN
-        };
-        mq::draw_rectangle(x_coord as f32, y_coord as f32, square_width as f32, square_width as f32, color);
+    pub fn set_cost(&mut self, cost: u8) {
+        self.cost = cost;
+    }
+
+
+    pub fn set_direction(&mut self, direction: Option<Direction>) {
+        self.direction = direction;
+    }
+
+
+    // Blank squares are shaded from white to brown by their terrain cost,
+    // and one-way squares are gray under their arrow
+    fn get_color(&self) -> mq::Color {
+        if self.square_type == SquareType::Blank && self.direction.is_some() {
+            return mq::LIGHTGRAY
+        }
+        if self.square_type == SquareType::Blank && self.cost > 1 {
+            let t = (self.cost - 1) as f32 / (MAX_COST - 1) as f32;
+            return mq::Color::new(0.95 - 0.55 * t, 0.9 - 0.65 * t, 0.75 - 0.7 * t, 1.0);
+        }
+        square_color(&self.square_type)
+    }
+}
+
+
+fn square_color(square_type: &SquareType) -> mq::Color {
+    match square_type {
+        SquareType::Blank => mq::WHITE,
+        SquareType::Wall=> mq::BLACK,
+        SquareType::Start=> mq::GREEN,
+        SquareType::Objective => mq::RED,
+        SquareType::Open => mq::SKYBLUE,
+        SquareType::Visited => mq::BLUE,
+        SquareType::Solution => mq::BROWN,
+        SquareType::Portal(id) => portal_color(*id)
+    }
+}
+
+
+fn portal_color(id: usize) -> mq::Color {
+    let colors = [mq::PURPLE, mq::ORANGE, mq::MAGENTA, mq::GOLD, mq::PINK, mq::VIOLET];
+    colors[id % colors.len()]
+}
+
+
+// See-through, so the terrain still shows under it
+fn component_color(index: usize) -> mq::Color {
+    let colors = [mq::SKYBLUE, mq::ORANGE, mq::LIME, mq::PINK, mq::GOLD, mq::VIOLET, mq::BEIGE, mq::MAROON];
+    let color = colors[index % colors.len()];
+    mq::Color::new(color.r, color.g, color.b, 0.6)
+}
+
+
+fn agent_color(index: usize) -> mq::Color {
+    let colors = [mq::BLUE, mq::MAGENTA, mq::LIME, mq::GOLD, mq::VIOLET, mq::SKYBLUE, mq::PINK, mq::DARKBROWN];
+    colors[index % colors.len()]
+}
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HeatmapMode {
+    Off,
+    ExpansionOrder,
+    PathCost
+}
+
+
+impl HeatmapMode {
+    fn next(&self) -> HeatmapMode {
+        match self {
+            HeatmapMode::Off => HeatmapMode::ExpansionOrder,
+            HeatmapMode::ExpansionOrder => HeatmapMode::PathCost,
+            HeatmapMode::PathCost => HeatmapMode::Off
+        }
+    }
+}
+
+
+// Blue for 0, then cyan, green, yellow and red for 1
+fn heat_color(t: f32) -> mq::Color {
+    let stops = [mq::BLUE, mq::SKYBLUE, mq::GREEN, mq::YELLOW, mq::RED];
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (position as usize).min(stops.len() - 2);
+    let local = position - i as f32;
+    let (a, b) = (stops[i], stops[i + 1]);
+    mq::Color::new(a.r + (b.r - a.r) * local, a.g + (b.g - a.g) * local, a.b + (b.b - a.b) * local, 1.0)
+}
+
+
+// A flow field to the goal and the square of every unit following it at every tick
+struct Crowd {
+    field: FlowField,
+    units: Vec<Vec<(usize, usize)>>,
+}
+
+
+// The abstract graph of the board and a search through it
+struct Abstraction {
+    hierarchy: Hierarchy,
+    result: HierarchicalResult,
+}
+
+
+// Distances between every two open squares and the one they are shown from
+struct Distances {
+    // None when the board has too many open squares
+    table: Option<DistanceTable>,
+    source: Option<(usize, usize)>,
+}
+
+
+// Groups of squares that can reach each other
+struct Components {
+    groups: Vec<Vec<(usize, usize)>>,
+}
+
+
+// What a replay is of
+enum ReplayKind {
+    Generation,
+    Search(SearchResult),
+    // Space-time searches, pursuits, multi-agent plans and crowds replay time
+    // steps instead of expanded nodes
+    SpaceTime(SpaceTimeResult),
+    Pursuit(PursuitResult),
+    MultiAgent(MultiAgentResult),
+    Crowd(Crowd),
+    // The rest only have one state
+    Abstraction(Abstraction),
+    Distances(Distances),
+    Components(Components),
+}
+
+
+// A finished search or maze generation and the board after every one of its steps
+struct Replay {
+    kind: ReplayKind,
+    states: Vec<Board>,
+    // Step at which every node of a search entered the open set
+    discovered_at: HashMap<(usize, usize), usize>,
+}
+
+
+impl Replay {
+    fn new(kind: ReplayKind, states: Vec<Board>) -> Replay {
+        let mut discovered_at: HashMap<(usize, usize), usize> = HashMap::new();
+        if let ReplayKind::Search(result) = &kind {
+            for (i, step) in result.steps.iter().enumerate() {
+                for sq in &step.opened {
+                    discovered_at.insert(*sq, i);
+                }
+            }
+        }
+        Replay { kind, states, discovered_at }
+    }
+
+
+    fn result(&self) -> Option<&SearchResult> {
+        match &self.kind {
+            ReplayKind::Search(result) => Some(result),
+            _ => None
+        }
+    }
+
+
+    // Multi-agent plans and crowds keep the same board at every time step, so
+    // they only store it once and take their length from the payload
+    fn length(&self) -> usize {
+        match &self.kind {
+            ReplayKind::MultiAgent(result) => result.makespan() + 1,
+            ReplayKind::Crowd(crowd) => crowd.units.iter().map(|path| path.len()).max().unwrap_or(1),
+            _ => self.states.len()
+        }
+    }
+
+
+    // States past the end of a shorter replay keep showing its last state
+    fn get_state(&self, index: usize) -> &Board {
+        &self.states[index.min(self.states.len() - 1)]
     }
 }
 
@@ -70,8 +263,23 @@ pub struct SquareCollection {
     objective_square_set: bool,
     start_square: (usize, usize),
     start_square_set: bool,
-    states: Vec<Vec<Vec<SquareType>>>,
+    // One replay per algorithm, more than one when comparing them
+    replays: Vec<Replay>,
     current_state: usize,
+    show_parents: bool,
+    heatmap: HeatmapMode,
+    topology: Topology,
+    wrap: bool,
+    portal_cost: usize,
+    next_portal: usize,
+    // Portal placed and waiting for the other end
+    pending_portal: Option<(usize, usize)>,
+    patrols: Vec<Patrol>,
+    agents: Vec<Agent>,
+    // Start placed and waiting for the goal of its agent
+    pending_agent: Option<(usize, usize)>,
+    // Why the last search asked for didn't run
+    skipped: Option<Outcome>,
 }
 
 
@@ -79,15 +287,26 @@ impl SquareCollection {
     pub fn new(square_width: usize, top_offset: usize, screen_width: f32, screen_height: f32) -> SquareCollection {
         let squares: Vec<Vec<Square>> = SquareCollection::build_squares(square_width, top_offset, screen_width, screen_height);
         SquareCollection {
-            square_width: square_width,
-            top_offset: top_offset,
-            squares: squares,
+            square_width,
+            top_offset,
+            squares,
             objective_square: (0, 0),
             objective_square_set: false,
             start_square: (0, 0),
             start_square_set: false,
-            states: Vec::new(),
+            replays: Vec::new(),
             current_state: 0,
+            show_parents: false,
+            heatmap: HeatmapMode::Off,
+            topology: Topology::Square,
+            wrap: false,
+            portal_cost: 1,
+            next_portal: 0,
+            pending_portal: None,
+            patrols: Vec::new(),
+            agents: Vec::new(),
+            pending_agent: None,
+            skipped: None,
         }
     }
 
@@ -112,28 +331,916 @@ impl SquareCollection {
 
 
     pub fn draw_squares(&self) {
+        if self.is_comparing() {
+            self.draw_comparison();
+            self.draw_heatmap_legend();
+            return
+        }
+        let (x_origin, y_origin, width) = (0f32, self.top_offset as f32, self.square_width as f32);
         for column in &self.squares {
             for square in column {
-                square.draw(self.square_width as usize, self.top_offset as usize);
+                self.draw_cell((square.x_grid, square.y_grid), x_origin, y_origin, width, 0f32, square.get_color());
+            }
+        }
+        self.draw_one_way_arrows(&self.squares, x_origin, y_origin, width);
+        self.draw_portal_links(&self.squares, x_origin, y_origin, width);
+
+        // Grid
+        if self.topology.is_hex() {
+            for column in &self.squares {
+                for square in column {
+                    self.draw_cell_lines((square.x_grid, square.y_grid), x_origin, y_origin, width, 2f32, mq::BLACK);
+                }
+            }
+        } else {
+            self.draw_square_lines();
+        }
+
+        if let Some(replay) = self.single_replay() {
+            self.draw_search_overlay(replay, x_origin, y_origin, width);
+        }
+        self.draw_patrols(x_origin, y_origin, width);
+        self.draw_pursuit(x_origin, y_origin, width);
+        self.draw_agents(x_origin, y_origin, width);
+        self.draw_crowd(x_origin, y_origin, width);
+        self.draw_abstraction(x_origin, y_origin, width);
+        self.draw_distances(x_origin, y_origin, width);
+        self.draw_components(x_origin, y_origin, width);
+        self.draw_heatmap_legend();
+    }
+
+
+    // Patrol routes and obstacles, plus the agent of a space-time search,
+    // at the time step being replayed
+    fn draw_patrols(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let result = self.get_space_time_result();
+        let time = if result.is_some() || self.get_pursuit_result().is_some() { self.current_state } else { 0 };
+        let radius = self.get_token_radius(width);
+        for patrol in &self.patrols {
+            for pair in patrol.route.windows(2) {
+                let from = self.get_center(pair[0], x_origin, y_origin, width);
+                let to = self.get_center(pair[1], x_origin, y_origin, width);
+                mq::draw_line(from.x, from.y, to.x, to.y, 2f32, mq::ORANGE);
+            }
+            let center = self.get_center(patrol.position_at(time), x_origin, y_origin, width);
+            mq::draw_circle(center.x, center.y, radius, mq::ORANGE);
+            mq::draw_circle_lines(center.x, center.y, radius, 2f32, mq::BLACK);
+        }
+        if let Some(result) = result.filter(|result| result.found()) {
+            let agent = result.path[time.min(result.path.len() - 1)];
+            let center = self.get_center(agent, x_origin, y_origin, width);
+            mq::draw_circle(center.x, center.y, radius, mq::DARKGREEN);
+            mq::draw_circle_lines(center.x, center.y, radius, 2f32, mq::BLACK);
+        }
+    }
+
+
+    // Agent and target of a pursuit at the tick being replayed
+    fn draw_pursuit(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let result = match self.get_pursuit_result() {
+            Some(result) => result,
+            None => return
+        };
+        let tick = self.current_state.min(result.ticks());
+        let radius = self.get_token_radius(width);
+        for (sq, color) in [(result.target[tick], mq::RED), (result.agent[tick], mq::DARKGREEN)] {
+            let center = self.get_center(sq, x_origin, y_origin, width);
+            mq::draw_circle(center.x, center.y, radius, color);
+            mq::draw_circle_lines(center.x, center.y, radius, 2f32, mq::BLACK);
+        }
+    }
+
+
+    // Goal of every agent outlined in its color and the agent on its start,
+    // or walking its path at the time step of a multi-agent replay
+    fn draw_agents(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let result = self.get_multi_agent_result().filter(|result| result.found());
+        let radius = self.get_token_radius(width);
+        for (i, agent) in self.agents.iter().enumerate() {
+            let color = agent_color(i);
+            self.draw_cell_lines(agent.goal, x_origin, y_origin, width, (width / 6.0).max(3f32), color);
+            let position = match result {
+                Some(result) => {
+                    let path = &result.paths[i];
+                    for pair in path.windows(2) {
+                        let from = self.get_center(pair[0], x_origin, y_origin, width);
+                        let to = self.get_center(pair[1], x_origin, y_origin, width);
+                        mq::draw_line(from.x, from.y, to.x, to.y, 3f32, color);
+                    }
+                    result.position_at(i, self.current_state)
+                },
+                None => agent.start
+            };
+            let center = self.get_center(position, x_origin, y_origin, width);
+            mq::draw_circle(center.x, center.y, radius, color);
+            mq::draw_circle_lines(center.x, center.y, radius, 2f32, mq::BLACK);
+        }
+        if let Some(start) = self.pending_agent {
+            let center = self.get_center(start, x_origin, y_origin, width);
+            mq::draw_circle_lines(center.x, center.y, radius, 3f32, agent_color(self.agents.len()));
+        }
+    }
+
+
+    // The first press places the start of a new agent, the second one its
+    // goal. Pressing on the start or goal of an agent removes it.
+    pub fn place_agent(&mut self, mouse_pos: (f32, f32)) {
+        if mouse_pos.1 <= self.top_offset as f32 { return }
+        let sq = self.get_square_from_mouse(mouse_pos);
+        if self.pending_agent == Some(sq) {
+            self.pending_agent = None;
+            return
+        }
+        if let Some(index) = self.agents.iter().position(|agent| agent.start == sq || agent.goal == sq) {
+            self.clear_results();
+            self.agents.remove(index);
+            return
+        }
+        if self.squares[sq.0][sq.1].square_type == SquareType::Wall { return }
+        match self.pending_agent {
+            Some(start) => {
+                self.clear_results();
+                self.agents.push(Agent { start, goal: sq });
+                self.pending_agent = None;
+            },
+            None => self.pending_agent = Some(sq)
+        }
+    }
+
+
+    // Plans every agent together, then plays them walking from the first time step
+    pub fn solve_agents(&mut self, solver: Solver) {
+        self.clear_results();
+        if self.agents.is_empty() { return }
+        let result = multi_agent::solve(&self.get_grid(), &self.agents, solver);
+        let states = vec![self.squares.clone()];
+        self.replays.push(Replay::new(ReplayKind::MultiAgent(result), states));
+        self.set_replay_state(0);
+    }
+
+
+    pub fn get_multi_agent_result(&self) -> Option<&MultiAgentResult> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::MultiAgent(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    // Flow direction of every square and the units still on their way at the
+    // tick being replayed
+    fn draw_crowd(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let crowd = match self.get_crowd() {
+            Some(crowd) => crowd,
+            None => return
+        };
+        for (sq, next) in &crowd.field.directions {
+            self.draw_arrow(*sq, *next, x_origin, y_origin, width, mq::DARKGRAY);
+        }
+        let radius = self.get_token_radius(width) * 0.6;
+        for path in &crowd.units {
+            let sq = path[self.current_state.min(path.len() - 1)];
+            if sq == crowd.field.goal { continue }
+            let center = self.get_center(sq, x_origin, y_origin, width);
+            mq::draw_circle(center.x, center.y, radius, mq::DARKBLUE);
+            mq::draw_circle_lines(center.x, center.y, radius, 1f32, mq::WHITE);
+        }
+    }
+
+
+    // Builds the flow field to the goal and spawns the units on random squares
+    // that can reach it, then plays them following it from the first tick
+    pub fn spread_flow_field(&mut self, units: usize, seed: u64) {
+        self.clear_results();
+        if !self.objective_square_set { return }
+        let field = flow_field::build(&self.get_grid(), self.objective_square);
+        let units: Vec<Vec<(usize, usize)>> = field
+            .spawn_units(units, seed)
+            .into_iter()
+            .map(|sq| field.path_from(sq))
+            .collect();
+        let states = vec![self.squares.clone()];
+        self.replays.push(Replay::new(ReplayKind::Crowd(Crowd { field, units }), states));
+        self.set_replay_state(0);
+    }
+
+
+    fn get_crowd(&self) -> Option<&Crowd> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::Crowd(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    pub fn get_flow_field(&self) -> Option<&FlowField> {
+        self.get_crowd().map(|crowd| &crowd.field)
+    }
+
+
+    // Units at the goal at the tick being replayed, and all of them
+    pub fn get_crowd_progress(&self) -> (usize, usize) {
+        match self.get_crowd() {
+            Some(crowd) => {
+                let arrived = crowd.units.iter().filter(|path| path.len() <= self.current_state + 1).count();
+                (arrived, crowd.units.len())
+            },
+            None => (0, 0)
+        }
+    }
+
+
+    // Every other cluster shaded, the abstract graph in purple and the
+    // abstract path of the search in orange
+    fn draw_abstraction(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let abstraction = match self.get_abstraction() {
+            Some(abstraction) => abstraction,
+            None => return
+        };
+        let hierarchy = &abstraction.hierarchy;
+        for x in 0..self.squares.len() {
+            for y in 0..self.squares[0].len() {
+                let cluster = hierarchy.cluster_of((x, y));
+                if (cluster.0 + cluster.1) % 2 == 1 {
+                    self.draw_cell((x, y), x_origin, y_origin, width, 0f32, mq::Color::new(0.0, 0.0, 0.0, 0.08));
+                }
+            }
+        }
+        let center = |sq: (usize, usize)| self.get_center(sq, x_origin, y_origin, width);
+        for (from, edges) in &hierarchy.edges {
+            for (to, _) in edges {
+                let (a, b) = (center(*from), center(*to));
+                mq::draw_line(a.x, a.y, b.x, b.y, 1f32, mq::Color::new(0.5, 0.2, 0.6, 0.5));
+            }
+        }
+        for pair in abstraction.result.abstract_path.windows(2) {
+            let (a, b) = (center(pair[0]), center(pair[1]));
+            mq::draw_line(a.x, a.y, b.x, b.y, (width / 8.0).max(3f32), mq::ORANGE);
+        }
+        let radius = self.get_token_radius(width) * 0.4;
+        for node in &hierarchy.nodes {
+            let c = center(*node);
+            mq::draw_circle(c.x, c.y, radius, mq::DARKPURPLE);
+        }
+    }
+
+
+    // Builds the abstract graph of the board with clusters of the given size
+    // and finds the path between the start and the goal through it
+    pub fn search_hierarchical(&mut self, cluster_size: usize) {
+        self.clear_results();
+        if !self.objective_square_set || !self.start_square_set { return }
+        let grid = self.get_grid();
+        let hierarchy = Hierarchy::build(&grid, cluster_size);
+        let result = hierarchy.search(&grid, self.start_square, self.objective_square);
+        let mut board = self.squares.clone();
+        for sq in result.path.iter().filter(|sq| **sq != self.start_square && **sq != self.objective_square) {
+            board[sq.0][sq.1].set_square_type(SquareType::Solution);
+        }
+        self.replays.push(Replay::new(ReplayKind::Abstraction(Abstraction { hierarchy, result }), vec![board]));
+        self.set_replay_state(0);
+    }
+
+
+    fn get_abstraction(&self) -> Option<&Abstraction> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::Abstraction(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    pub fn get_hierarchical_result(&self) -> Option<(&Hierarchy, &HierarchicalResult)> {
+        self.get_abstraction().map(|abstraction| (&abstraction.hierarchy, &abstraction.result))
+    }
+
+
+    // Heatmap of the distance from the picked square to every square it reaches
+    fn draw_distances(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let (table, source) = match self.get_distances() {
+            Some(Distances { table: Some(table), source: Some(source) }) => (table, *source),
+            _ => return
+        };
+        let reached = table.distances_from(source);
+        let max = reached.iter().map(|(_, distance)| *distance).max().unwrap_or(0);
+        for (sq, distance) in &reached {
+            self.draw_cell(*sq, x_origin, y_origin, width, 1f32, heat_color(*distance as f32 / max.max(1) as f32));
+        }
+        self.draw_cell_lines(source, x_origin, y_origin, width, (width / 8.0).max(2f32), mq::YELLOW);
+        self.draw_legend("Distance from the square", max);
+    }
+
+
+    // Fills the distance table of the board, or leaves it if it was being shown
+    pub fn measure_distances(&mut self) {
+        let measuring = self.is_measuring();
+        self.clear_results();
+        if measuring { return }
+        let grid = self.get_grid();
+        let open_squares = (0..grid.width()).flat_map(|x| (0..grid.height()).map(move |y| (x, y))).filter(|sq| grid.is_walkable(*sq)).count();
+        let table = all_pairs::compute(&grid, Method::for_size(open_squares));
+        let states = vec![self.squares.clone()];
+        self.replays.push(Replay::new(ReplayKind::Distances(Distances { table, source: None }), states));
+    }
+
+
+    // Square the distances are shown from
+    pub fn pick_distance_source(&mut self, mouse_pos: (f32, f32)) {
+        let sq = match self.get_hovered_square(mouse_pos) {
+            Some(sq) => sq,
+            None => return
+        };
+        if let [Replay { kind: ReplayKind::Distances(distances), .. }] = self.replays.as_mut_slice() {
+            distances.source = Some(sq);
+        }
+    }
+
+
+    fn get_distances(&self) -> Option<&Distances> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::Distances(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    pub fn is_measuring(&self) -> bool {
+        self.get_distances().is_some()
+    }
+
+
+    // None also while measuring a board with too many open squares
+    pub fn get_distance_table(&self) -> Option<&DistanceTable> {
+        self.get_distances().and_then(|distances| distances.table.as_ref())
+    }
+
+
+    pub fn get_distance_source(&self) -> Option<(usize, usize)> {
+        self.get_distances().and_then(|distances| distances.source)
+    }
+
+
+    // Every group of squares that can reach each other in its own color
+    fn draw_components(&self, x_origin: f32, y_origin: f32, width: f32) {
+        let components = match self.get_components() {
+            Some(components) => components,
+            None => return
+        };
+        for (i, group) in components.groups.iter().enumerate() {
+            let color = component_color(i);
+            for sq in group {
+                if (self.start_square_set && *sq == self.start_square) || (self.objective_square_set && *sq == self.objective_square) { continue }
+                self.draw_cell(*sq, x_origin, y_origin, width, 1f32, color);
+            }
+        }
+    }
+
+
+    // Colors the components of the board, or stops if they were being shown
+    pub fn show_components(&mut self) {
+        let showing = self.get_components().is_some();
+        self.clear_results();
+        if showing { return }
+        let groups = self.get_grid().strong_components();
+        let states = vec![self.squares.clone()];
+        self.replays.push(Replay::new(ReplayKind::Components(Components { groups }), states));
+    }
+
+
+    fn get_components(&self) -> Option<&Components> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::Components(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    // Groups of squares that can reach each other, the largest first
+    pub fn get_component_groups(&self) -> Option<&[Vec<(usize, usize)>]> {
+        self.get_components().map(|components| components.groups.as_slice())
+    }
+
+
+    // Whether the last search was skipped because the start can't reach the goal
+    pub fn is_goal_unreachable(&self) -> bool {
+        self.skipped == Some(Outcome::Unreachable)
+    }
+
+
+    // Index of the group of the start and of the goal, when both are placed
+    pub fn get_endpoint_components(&self) -> Option<(usize, usize)> {
+        let groups = self.get_component_groups()?;
+        if !self.start_square_set || !self.objective_square_set { return None }
+        let group_of = |sq: (usize, usize)| groups.iter().position(|group| group.contains(&sq));
+        Some((group_of(self.start_square)?, group_of(self.objective_square)?))
+    }
+
+
+    // Circles standing for things moving over the board
+    fn get_token_radius(&self, width: f32) -> f32 {
+        if self.topology.is_hex() { self.get_hex_radius(width) * 0.6 } else { width * 0.35 }
+    }
+
+
+    // Adds the square to the route of the last patrol, walking there along the
+    // shortest way, or starts a new patrol on it
+    pub fn add_patrol_waypoint(&mut self, mouse_pos: (f32, f32), new_patrol: bool) {
+        if mouse_pos.1 <= self.top_offset as f32 { return }
+        let sq = self.get_square_from_mouse(mouse_pos);
+        if self.squares[sq.0][sq.1].square_type == SquareType::Wall { return }
+        let last = match self.patrols.last() {
+            Some(patrol) if !new_patrol => *patrol.route.last().unwrap(),
+            _ => {
+                self.patrols.push(Patrol { route: vec![sq] });
+                return
+            }
+        };
+        let result = search::search(&self.get_grid(), last, sq, Algorithm::Dijkstra);
+        if let Some(patrol) = self.patrols.last_mut() {
+            patrol.route.extend(result.path.into_iter().skip(1));
+        }
+    }
+
+
+    // Plans around the patrols, then plays the agent and the obstacles
+    // moving together from the first time step
+    pub fn search_space_time(&mut self) {
+        self.clear_results();
+        if !self.objective_square_set || !self.start_square_set { return }
+        let grid = self.get_grid();
+        let max_time = 2 * grid.width() * grid.height();
+        let result = space_time::search(&grid, self.start_square, self.objective_square, &self.patrols, max_time);
+        let mut board = self.squares.clone();
+        let mut states: Vec<Board> = Vec::new();
+        for sq in &result.path {
+            if *sq != self.start_square && *sq != self.objective_square {
+                board[sq.0][sq.1].set_square_type(SquareType::Solution);
+            }
+            states.push(board.clone());
+        }
+        if states.is_empty() {
+            states.push(board);
+        }
+        self.replays.push(Replay::new(ReplayKind::SpaceTime(result), states));
+        self.set_replay_state(0);
+    }
+
+
+    // Chases the goal with A*, which wanders around from where it is or walks
+    // the route of the last patrol, then plays the chase from the first tick
+    pub fn pursue_target(&mut self, follow_patrol: bool, seed: u64) {
+        self.clear_results();
+        if !self.start_square_set { return }
+        let motion = match self.patrols.last() {
+            Some(patrol) if follow_patrol => TargetMotion::Route(patrol.clone()),
+            _ if !follow_patrol && self.objective_square_set => TargetMotion::Wander { start: self.objective_square, seed },
+            _ => return
+        };
+        let grid = self.get_grid();
+        let max_ticks = 2 * grid.width() * grid.height();
+        let result = pursuit::pursue(&grid, self.start_square, &motion, Algorithm::AStar, max_ticks);
+        // Walked squares stay brown, the path being followed is light blue
+        let mut trail = self.squares.clone();
+        let mut states: Vec<Board> = Vec::new();
+        for (agent, plan) in result.agent.iter().zip(&result.plans) {
+            if *agent != self.start_square && *agent != self.objective_square {
+                trail[agent.0][agent.1].set_square_type(SquareType::Solution);
+            }
+            let mut board = trail.clone();
+            for sq in plan.iter().skip(1) {
+                if board[sq.0][sq.1].square_type == SquareType::Blank {
+                    board[sq.0][sq.1].set_square_type(SquareType::Open);
+                }
+            }
+            states.push(board);
+        }
+        self.replays.push(Replay::new(ReplayKind::Pursuit(result), states));
+        self.set_replay_state(0);
+    }
+
+
+    pub fn get_pursuit_result(&self) -> Option<&PursuitResult> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::Pursuit(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    pub fn get_space_time_result(&self) -> Option<&SpaceTimeResult> {
+        match self.single_replay().map(|replay| &replay.kind) {
+            Some(ReplayKind::SpaceTime(payload)) => Some(payload),
+            _ => None
+        }
+    }
+
+
+    fn draw_square_lines(&self) {
+        let mut x = 0usize;
+        let mut y = self.top_offset;
+        let thickness = 2f32;
+        let line_color = mq::BLACK;
+        let screen_width = mq::screen_width() as usize;
+        let screen_height = mq::screen_height() as usize;
+        while x <= screen_width {
+            mq::draw_line(x as f32, self.top_offset as f32, x as f32, mq::screen_height(), thickness, line_color);
+            x += self.square_width;
+        }
+        while y <= screen_height {
+            mq::draw_line(0f32, y as f32, mq::screen_width(), y as f32, thickness, line_color);
+            y += self.square_width;
+        }
+    }
+
+
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
+
+
+    // Drops the results, which were found with the old neighbors
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.clear_results();
+        self.topology = topology;
+    }
+
+
+    fn draw_one_way_arrows(&self, board: &Board, x_origin: f32, y_origin: f32, width: f32) {
+        let size = if self.topology.is_hex() { self.get_hex_radius(width) } else { width / 2.0 };
+        for column in board {
+            for square in column.iter().filter(|square| square.square_type != SquareType::Wall) {
+                let direction = match square.direction {
+                    Some(direction) => direction.vector(),
+                    None => continue
+                };
+                let center = self.get_center((square.x_grid, square.y_grid), x_origin, y_origin, width);
+                let direction = mq::vec2(direction.0 as f32, direction.1 as f32);
+                let side = mq::vec2(-direction.y, direction.x) * size * 0.45;
+                let tip = center + direction * size * 0.6;
+                let base = center - direction * size * 0.4;
+                mq::draw_triangle(tip, base + side, base - side, mq::DARKGRAY);
+            }
+        }
+    }
+
+
+    // Thin lines between linked portals
+    fn draw_portal_links(&self, board: &Board, x_origin: f32, y_origin: f32, width: f32) {
+        for (a, b) in SquareCollection::get_portal_pairs(board) {
+            let from = self.get_center(a, x_origin, y_origin, width);
+            let to = self.get_center(b, x_origin, y_origin, width);
+            let color = match board[a.0][a.1].square_type {
+                SquareType::Portal(id) => portal_color(id),
+                _ => continue
+            };
+            mq::draw_line(from.x, from.y, to.x, to.y, 2f32, color);
+        }
+    }
+
+
+    // Both ends of every portal that has them, by portal number
+    fn get_portal_pairs(board: &Board) -> Vec<((usize, usize), (usize, usize))> {
+        let mut ends: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for column in board {
+            for square in column {
+                if let SquareType::Portal(id) = square.square_type {
+                    ends.entry(id).or_default().push((square.x_grid, square.y_grid));
+                }
             }
         }
+        let mut pairs: Vec<((usize, usize), (usize, usize))> = ends
+            .into_values()
+            .filter(|ends| ends.len() == 2)
+            .map(|ends| (ends[0], ends[1]))
+            .collect();
+        pairs.sort();
+        pairs
     }
 
 
-    fn get_square_from_mouse(&self, mouse_pos: (f32, f32)) -> (f32, f32) {
-        let mouse_x = mouse_pos.0 / (self.square_width as f32);
-        let mouse_x = mouse_x.clamp(0f32, (self.squares.len() - 1) as f32);
-        let mouse_y = (mouse_pos.1 - (self.top_offset as f32)) / (self.square_width as f32);
-        let mouse_y = mouse_y.clamp(0f32, (self.squares[0].len() - 1) as f32);
-        (mouse_x, mouse_y)
+    pub fn set_portal_cost(&mut self, cost: usize) {
+        self.clear_results();
+        self.portal_cost = cost;
+    }
+
+
+    // The first press places a portal, the second one links another square to
+    // it. Pressing on the waiting portal again removes it.
+    pub fn place_portal(&mut self, mouse_pos: (f32, f32)) {
+        if mouse_pos.1 <= self.top_offset as f32 { return }
+        let sq = self.get_square_from_mouse(mouse_pos);
+        if self.pending_portal == Some(sq) {
+            self.change_square_type(mouse_pos, SquareType::Blank);
+            return
+        }
+        let id = match self.pending_portal {
+            Some(pending) => match self.squares[pending.0][pending.1].square_type {
+                SquareType::Portal(id) => id,
+                _ => return
+            },
+            None => {
+                self.next_portal += 1;
+                self.next_portal - 1
+            }
+        };
+        self.change_square_type(mouse_pos, SquareType::Portal(id));
+        self.pending_portal = match self.pending_portal {
+            Some(_) => None,
+            None => Some(sq)
+        };
+    }
+
+
+    pub fn toggle_wrap(&mut self) {
+        self.clear_results();
+        self.wrap = !self.wrap;
+    }
+
+
+    // Hexes as big as they can be while the board keeps the size it has with squares
+    fn get_hex_radius(&self, width: f32) -> f32 {
+        let board_width = self.squares.len() as f32 * width;
+        let board_height = self.squares[0].len() as f32 * width;
+        let (columns, rows) = (self.squares.len() as f32, self.squares[0].len() as f32);
+        let sqrt_3 = 3f32.sqrt();
+        match self.topology {
+            Topology::HexFlat => (board_width / (1.5 * columns + 0.5)).min(board_height / (sqrt_3 * (rows + 0.5))),
+            _ => (board_width / (sqrt_3 * (columns + 0.5))).min(board_height / (1.5 * rows + 0.5))
+        }
+    }
+
+
+    // Center of a square in a board drawn from the given origin with squares of the given width
+    fn get_center(&self, sq: (usize, usize), x_origin: f32, y_origin: f32, width: f32) -> mq::Vec2 {
+        let (x, y) = (sq.0 as f32, sq.1 as f32);
+        let radius = self.get_hex_radius(width);
+        let sqrt_3 = 3f32.sqrt();
+        let center = match self.topology {
+            Topology::Square => mq::vec2((x + 0.5) * width, (y + 0.5) * width),
+            Topology::HexPointy => mq::vec2(sqrt_3 * radius * (x + 0.5 * (sq.1 % 2) as f32 + 0.5), radius * (1.5 * y + 1.0)),
+            Topology::HexFlat => mq::vec2(radius * (1.5 * x + 1.0), sqrt_3 * radius * (y + 0.5 * (sq.0 % 2) as f32 + 0.5))
+        };
+        center + mq::vec2(x_origin, y_origin)
+    }
+
+
+    // Distance between a square and the same square in the next board to
+    // the right and below, were the board repeated like a wrapped grid does
+    fn get_board_period(&self, width: f32) -> mq::Vec2 {
+        let (columns, rows) = (self.squares.len() as f32, self.squares[0].len() as f32);
+        let radius = self.get_hex_radius(width);
+        let sqrt_3 = 3f32.sqrt();
+        match self.topology {
+            Topology::Square => mq::vec2(columns * width, rows * width),
+            Topology::HexPointy => mq::vec2(sqrt_3 * radius * columns, 1.5 * radius * rows),
+            Topology::HexFlat => mq::vec2(1.5 * radius * columns, sqrt_3 * radius * rows)
+        }
+    }
+
+
+    // Rotation of the hexagons for macroquad, 0 having a corner pointing right
+    fn get_hex_rotation(&self) -> f32 {
+        if self.topology == Topology::HexPointy { 30f32 } else { 0f32 }
+    }
+
+
+    // Inset shrinks the cell so the grid lines around it stay visible
+    fn draw_cell(&self, sq: (usize, usize), x_origin: f32, y_origin: f32, width: f32, inset: f32, color: mq::Color) {
+        if self.topology.is_hex() {
+            let center = self.get_center(sq, x_origin, y_origin, width);
+            mq::draw_poly(center.x, center.y, 6, self.get_hex_radius(width) - inset, self.get_hex_rotation(), color);
+        } else {
+            let x_coord = x_origin + sq.0 as f32 * width + inset;
+            let y_coord = y_origin + sq.1 as f32 * width + inset;
+            mq::draw_rectangle(x_coord, y_coord, width - 2f32 * inset, width - 2f32 * inset, color);
+        }
+    }
+
+
+    fn draw_cell_lines(&self, sq: (usize, usize), x_origin: f32, y_origin: f32, width: f32, thickness: f32, color: mq::Color) {
+        if self.topology.is_hex() {
+            let center = self.get_center(sq, x_origin, y_origin, width);
+            mq::draw_poly_lines(center.x, center.y, 6, self.get_hex_radius(width), self.get_hex_rotation(), thickness, color);
+        } else {
+            let x_coord = x_origin + sq.0 as f32 * width;
+            let y_coord = y_origin + sq.1 as f32 * width;
+            mq::draw_rectangle_lines(x_coord, y_coord, width, width, thickness, color);
+        }
+    }
+
+
+    // Heatmap, parent arrows of the discovered nodes and the node being expanded
+    fn draw_search_overlay(&self, replay: &Replay, x_origin: f32, y_origin: f32, width: f32) {
+        let result = match replay.result() {
+            Some(result) => result,
+            None => return
+        };
+        let center = |sq: (usize, usize)| self.get_center(sq, x_origin, y_origin, width);
+        if self.heatmap != HeatmapMode::Off {
+            let state = replay.get_state(self.current_state);
+            let max = self.get_heatmap_max().max(1) as f32;
+            for (sq, discovered) in &replay.discovered_at {
+                let square_type = &state[sq.0][sq.1].square_type;
+                if *square_type != SquareType::Open && *square_type != SquareType::Visited { continue }
+                let value = match self.heatmap {
+                    HeatmapMode::ExpansionOrder => match result.expanded_at.get(sq) {
+                        Some(index) if *index <= self.current_state => *index,
+                        _ => continue
+                    },
+                    _ if *discovered <= self.current_state => result.path_costs[sq],
+                    _ => continue
+                };
+                self.draw_cell(*sq, x_origin, y_origin, width, 1f32, heat_color(value as f32 / max));
+            }
+        }
+        if self.show_parents {
+            for (sq, parent) in &result.came_from {
+                if replay.discovered_at[sq] > self.current_state { continue }
+                self.draw_arrow(*sq, *parent, x_origin, y_origin, width, mq::DARKGRAY);
+            }
+        }
+        // Jumps through portals once the path is shown
+        if result.found() && self.current_state >= result.steps.len() {
+            let state = replay.get_state(self.current_state);
+            for pair in result.path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let linked = matches!(state[a.0][a.1].square_type, SquareType::Portal(id) if state[b.0][b.1].square_type == SquareType::Portal(id));
+                if linked {
+                    let (from, to) = (center(a), center(b));
+                    mq::draw_line(from.x, from.y, to.x, to.y, (width / 6.0).max(3f32), mq::BROWN);
+                }
+            }
+        }
+        if let Some(step) = result.steps.get(self.current_state) {
+            self.draw_cell_lines(step.current, x_origin, y_origin, width, (width / 8.0).max(2f32), mq::YELLOW);
+        }
+    }
+
+
+    // Arrow from a square towards a neighbor, stopping short of its center so
+    // arrows don't touch
+    fn draw_arrow(&self, sq: (usize, usize), neighbor: (usize, usize), x_origin: f32, y_origin: f32, width: f32, color: mq::Color) {
+        let from = self.get_center(sq, x_origin, y_origin, width);
+        let mut to = self.get_center(neighbor, x_origin, y_origin, width);
+        // Neighbors across a wrapped edge are pointed at past that edge
+        if self.wrap {
+            let period = self.get_board_period(width);
+            let copies = (-1..=1).flat_map(|i| (-1..=1).map(move |j| mq::vec2(i as f32, j as f32)));
+            to = copies
+                .map(|copy| to + copy * period)
+                .min_by(|a, b| a.distance(from).total_cmp(&b.distance(from)))
+                .unwrap();
+        }
+        let distance = (to - from).length();
+        let direction = (to - from).normalize_or_zero();
+        let tip = from + direction * distance * 0.55;
+        let side = mq::vec2(-direction.y, direction.x) * distance * 0.15;
+        let base = tip - direction * distance * 0.25;
+        mq::draw_line(from.x, from.y, base.x, base.y, 2f32, color);
+        mq::draw_triangle(tip, base + side, base - side, color);
+    }
+
+
+    pub fn toggle_parents(&mut self) {
+        self.show_parents = !self.show_parents;
+    }
+
+
+    pub fn cycle_heatmap(&mut self) {
+        self.heatmap = self.heatmap.next();
+    }
+
+
+    // Value shown in red, shared by every replay so they can be compared
+    fn get_heatmap_max(&self) -> usize {
+        self.replays
+            .iter()
+            .filter_map(Replay::result)
+            .map(|result| match self.heatmap {
+                HeatmapMode::ExpansionOrder => result.nodes_expanded().saturating_sub(1),
+                _ => result.path_costs.values().copied().max().unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+
+    fn draw_heatmap_legend(&self) {
+        if self.heatmap == HeatmapMode::Off || self.replays.iter().all(|replay| replay.result().is_none()) { return }
+        let title = match self.heatmap {
+            HeatmapMode::ExpansionOrder => "Expansion order",
+            _ => "Path cost (g)"
+        };
+        self.draw_legend(title, self.get_heatmap_max());
+    }
+
+
+    // Gradient and its range, at the bottom right corner of the window
+    fn draw_legend(&self, title: &str, max: usize) {
+        let (width, height) = (300f32, 70f32);
+        let x = mq::screen_width() - width - 15f32;
+        let y = mq::screen_height() - height - 15f32;
+        mq::draw_rectangle(x, y, width, height, mq::Color::new(1.0, 1.0, 1.0, 0.9));
+        mq::draw_rectangle_lines(x, y, width, height, 2f32, mq::BLACK);
+        mq::draw_text(title, x + 10f32, y + 22f32, 24f32, mq::BLACK);
+        let bar_width = width - 20f32;
+        let slices = 50;
+        for i in 0..slices {
+            let t = i as f32 / (slices - 1) as f32;
+            let slice_width = bar_width / slices as f32;
+            mq::draw_rectangle(x + 10f32 + i as f32 * slice_width, y + 30f32, slice_width + 1f32, 14f32, heat_color(t));
+        }
+        let max = max.to_string();
+        let max_width = mq::measure_text(&max, None, 20, 1.0).width;
+        mq::draw_text("0", x + 10f32, y + 62f32, 20f32, mq::BLACK);
+        mq::draw_text(&max, x + width - 10f32 - max_width, y + 62f32, 20f32, mq::BLACK);
+    }
+
+
+    // Every replay in its own viewport, laid out in a grid below the top panel
+    fn draw_comparison(&self) {
+        let columns = (self.replays.len() as f32).sqrt().ceil() as usize;
+        let rows = self.replays.len().div_ceil(columns);
+        let viewport_width = mq::screen_width() / columns as f32;
+        let viewport_height = (mq::screen_height() - self.top_offset as f32) / rows as f32;
+        let label_height = 30f32;
+        let x_squares = self.squares.len() as f32;
+        let y_squares = self.squares[0].len() as f32;
+        let width = ((viewport_width - 10f32) / x_squares).min((viewport_height - label_height - 10f32) / y_squares).floor();
+
+        for (i, replay) in self.replays.iter().enumerate() {
+            let x_origin = (i % columns) as f32 * viewport_width + 5f32;
+            let y_origin = self.top_offset as f32 + (i / columns) as f32 * viewport_height + label_height;
+            let state = replay.get_state(self.current_state);
+            for (x, column) in state.iter().enumerate() {
+                for (y, square) in column.iter().enumerate() {
+                    self.draw_cell((x, y), x_origin, y_origin, width, 0f32, square.get_color());
+                    self.draw_cell_lines((x, y), x_origin, y_origin, width, 1f32, mq::LIGHTGRAY);
+                }
+            }
+            self.draw_one_way_arrows(state, x_origin, y_origin, width);
+            self.draw_portal_links(state, x_origin, y_origin, width);
+            mq::draw_rectangle_lines(x_origin, y_origin, x_squares * width, y_squares * width, 2f32, mq::BLACK);
+            self.draw_search_overlay(replay, x_origin, y_origin, width);
+
+            let result = match replay.result() {
+                Some(result) => result,
+                None => continue
+            };
+            let expanded = result.steps.len().min(self.current_state + 1);
+            let mut label = format!("{}: {expanded} / {} expanded", result.algorithm.name(), result.nodes_expanded());
+            if result.found() {
+                label += &format!(", path cost {}", result.cost);
+            } else {
+                label += ", no path";
+            }
+            mq::draw_text(&label, x_origin, y_origin - 8f32, 26f32, mq::BLACK);
+        }
+    }
+
+
+    fn get_square_from_mouse(&self, mouse_pos: (f32, f32)) -> (usize, usize) {
+        let width = self.square_width as f32;
+        let (x_max, y_max) = (self.squares.len() - 1, self.squares[0].len() - 1);
+        if !self.topology.is_hex() {
+            let mouse_x = (mouse_pos.0 / width).clamp(0f32, x_max as f32);
+            let mouse_y = ((mouse_pos.1 - self.top_offset as f32) / width).clamp(0f32, y_max as f32);
+            return (mouse_x as usize, mouse_y as usize)
+        }
+        // Rough guess ignoring the shifted rows or columns, then the hex
+        // with the nearest center around it, which is the one under the mouse
+        let mouse = mq::vec2(mouse_pos.0, mouse_pos.1 - self.top_offset as f32);
+        let radius = self.get_hex_radius(width);
+        let sqrt_3 = 3f32.sqrt();
+        let (guess_x, guess_y) = match self.topology {
+            Topology::HexFlat => ((mouse.x - radius) / (1.5 * radius), mouse.y / (sqrt_3 * radius) - 0.5),
+            _ => (mouse.x / (sqrt_3 * radius) - 0.5, (mouse.y - radius) / (1.5 * radius))
+        };
+        let guess_x = (guess_x.round().max(0f32) as usize).min(x_max);
+        let guess_y = (guess_y.round().max(0f32) as usize).min(y_max);
+        let mut nearest = (guess_x, guess_y);
+        for x in guess_x.saturating_sub(1)..=(guess_x + 1).min(x_max) {
+            for y in guess_y.saturating_sub(1)..=(guess_y + 1).min(y_max) {
+                let distance = self.get_center((x, y), 0f32, 0f32, width).distance(mouse);
+                if distance < self.get_center(nearest, 0f32, 0f32, width).distance(mouse) {
+                    nearest = (x, y);
+                }
+            }
+        }
+        nearest
+    }
+
+
+    // Board square under the mouse, if it is over the board
+    pub fn get_hovered_square(&self, mouse_pos: (f32, f32)) -> Option<(usize, usize)> {
+        if self.is_comparing() || mouse_pos.1 <= self.top_offset as f32 { return None }
+        Some(self.get_square_from_mouse(mouse_pos))
     }
 
 
     pub fn change_square_type(&mut self, mouse_pos: (f32, f32), square_type: SquareType) {
         if mouse_pos.1 > self.top_offset as f32 {
-            let (mouse_x, mouse_y): (f32, f32) = self.get_square_from_mouse(mouse_pos);
-            let mouse_x_index = mouse_x as usize;
-            let mouse_y_index = mouse_y as usize;
+            let (mouse_x_index, mouse_y_index) = self.get_square_from_mouse(mouse_pos);
             let previous_type = self.squares[mouse_x_index][mouse_y_index].get_square_type().clone();
             match square_type {
                 SquareType::Start => {
@@ -152,132 +1259,182 @@ impl SquareCollection {
                 },
                 _ => {  }
             }
+            // A portal can't stay linked to a square that is not one anymore
+            if let SquareType::Portal(id) = previous_type {
+                if square_type != previous_type {
+                    self.remove_portal(id);
+                }
+            }
             self.squares[mouse_x_index][mouse_y_index].set_square_type(square_type.clone());
-            match previous_type {
-                SquareType::Start | SquareType::Objective => {
-                    if square_type != previous_type {
-                        match previous_type {
-                            SquareType::Start => { self.start_square_set = false; }
-                            SquareType::Objective => { self.objective_square_set = false; }
-                            _ => {  }
-                        }
-                    }
+            if square_type == SquareType::Blank {
+                self.squares[mouse_x_index][mouse_y_index].set_cost(1);
+                self.squares[mouse_x_index][mouse_y_index].set_direction(None);
+            }
+            if square_type != previous_type {
+                match previous_type {
+                    SquareType::Start => { self.start_square_set = false; }
+                    SquareType::Objective => { self.objective_square_set = false; }
+                    _ => {  }
                 }
-                _ => {  }
             }
         }
     }
 
 
-    fn manhattan_distance(&self, sq: &(usize, usize)) -> usize {
-        let x_dist: isize = sq.0 as isize - self.objective_square.0 as isize;
-        let y_dist: isize = sq.1 as isize - self.objective_square.1 as isize;
-        let result = (x_dist.abs() + y_dist.abs()) as usize;
-        result
+    // Turns a blank square into a one-way square pointing up, then rotates
+    // it clockwise and finally makes it a normal square again
+    pub fn rotate_one_way(&mut self, mouse_pos: (f32, f32)) {
+        if mouse_pos.1 <= self.top_offset as f32 { return }
+        let (x, y) = self.get_square_from_mouse(mouse_pos);
+        let square = &mut self.squares[x][y];
+        if square.square_type != SquareType::Blank { return }
+        let direction = match square.direction {
+            None => Some(Direction::Up),
+            Some(Direction::Left) => None,
+            Some(direction) => Some(direction.next())
+        };
+        square.set_direction(direction);
+        square.set_cost(1);
     }
 
 
-    // https://en.wikipedia.org/wiki/A*_search_algorithm
-    // true true  -> A*
-    // true false -> Dijkstra
-    // false true -> Greedy Best first
-    pub fn search_algorithm(&mut self, weights: bool, heuristics: bool) {
-        self.clear_results();
-        if !self.objective_square_set || !self.start_square_set { return }
-        let x_dim = self.squares.len() - 1;
-        let y_dim = self.squares[0].len() - 1;
-        let mut open_set: HashSet<(usize, usize)> = HashSet::new();
-        let mut closed_set: HashSet<(usize, usize)> = HashSet::new();
-        let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
-        let mut path_costs: HashMap<(usize, usize), usize> = HashMap::new(); // gScore
-        let mut scores: HashMap<(usize, usize), usize> = HashMap::new(); // fScore
-
-        open_set.insert(self.start_square);
-        path_costs.insert(self.start_square, 0);
-        scores.insert(self.start_square, self.manhattan_distance(&self.start_square));
-
-        'main_loop: while !open_set.is_empty() {
-            let mut current = self.get_lowest_score(&scores, &open_set, heuristics);
-            if current.0 == self.objective_square.0 && current.1 == self.objective_square.1 {
-                loop {
-                    current = came_from[&current];
-                    if current.0 == self.start_square.0 && current.1 == self.start_square.1 {
-                        self.states.push(self.get_current_state());
-                        break 'main_loop;
-                    }
-                    self.squares[current.0][current.1].set_square_type(SquareType::Solution);
+    // Walls and terrain of the current board, ready to be searched
+    fn get_grid(&self) -> Grid {
+        let mut grid = Grid::new(self.squares.len(), self.squares[0].len());
+        grid.set_topology(self.topology);
+        grid.set_wrap(self.wrap);
+        for x in 0..self.squares.len() {
+            for y in 0..self.squares[0].len() {
+                let square = &self.squares[x][y];
+                if square.square_type == SquareType::Wall {
+                    grid.set_cell((x, y), Cell::Wall);
+                } else if let Some(direction) = square.direction {
+                    grid.set_cell((x, y), Cell::OneWay(direction));
+                } else if square.cost > 1 {
+                    grid.set_cell((x, y), Cell::Weighted(square.cost));
                 }
             }
-            self.squares[current.0][current.1].set_square_type(SquareType::Visited);
+        }
+        for (a, b) in SquareCollection::get_portal_pairs(&self.squares) {
+            grid.add_portal(a, b);
+        }
+        grid.set_portal_cost(self.portal_cost);
+        grid
+    }
 
-            open_set.remove(&current);
-            closed_set.insert(current);
-            for neighbor in self.neighbors(&current, &x_dim, &y_dim, &closed_set) {
-                let mut tentative_path_cost = path_costs[&current];
-                if weights {
-                    tentative_path_cost += 1;
-                }
-                if open_set.contains(&neighbor) && tentative_path_cost < path_costs[&neighbor] {
-                    came_from.insert(neighbor, current);
-                    path_costs.insert(neighbor, tentative_path_cost);
-                    if heuristics {
-                        tentative_path_cost += self.manhattan_distance(&neighbor);
-                    }
-                    scores.insert(neighbor, tentative_path_cost);
-                }
-                if !open_set.contains(&neighbor){
-                    open_set.insert(neighbor);
-                    came_from.insert(neighbor, current);
-                    path_costs.insert(neighbor, tentative_path_cost);
-                    if heuristics {
-                        tentative_path_cost += self.manhattan_distance(&neighbor);
-                    }
-                    scores.insert(neighbor, tentative_path_cost);
-                }
+
+    pub fn search_algorithm(&mut self, algorithm: Algorithm) {
+        self.compare_algorithms(&[algorithm]);
+    }
+
+
+    // Solves the same board with every algorithm, replaying them side by side
+    pub fn compare_algorithms(&mut self, algorithms: &[Algorithm]) {
+        self.clear_results();
+        let grid = self.get_grid();
+        let start = if self.start_square_set { Some(self.start_square) } else { None };
+        let objective = if self.objective_square_set { Some(self.objective_square) } else { None };
+        let (start, objective) = match search::check_endpoints(&grid, start, objective) {
+            Ok(endpoints) => endpoints,
+            Err(outcome) => {
+                self.skipped = Some(outcome);
+                return
             }
-            self.states.push(self.get_current_state());
+        };
+        // Shows why instead of flooding everything the start can reach
+        if !grid.can_reach(start, objective) {
+            let states = vec![self.squares.clone()];
+            self.replays.push(Replay::new(ReplayKind::Components(Components { groups: grid.strong_components() }), states));
+            self.skipped = Some(Outcome::Unreachable);
+            return
         }
-        self.current_state = self.states.len() - 1;
+        for algorithm in algorithms {
+            let result = search::search(&grid, start, objective, *algorithm);
+            let states = self.build_states(&result);
+            self.replays.push(Replay::new(ReplayKind::Search(result), states));
+        }
+        self.current_state = self.get_replay_length() - 1;
+        self.load_state();
     }
 
 
-    fn neighbors(&self, current: &(usize, usize), x_max: &usize, y_max: &usize, closed_set: &HashSet<(usize, usize)>) -> Vec<(usize, usize)> {
-        let mut result: Vec<(usize, usize)> = Vec::new();
-        if current.1 != 0 {
-            let new = (current.0, current.1 - 1);
-            if !closed_set.contains(&new) && *self.squares[new.0][new.1].get_square_type() != SquareType::Wall {
-                result.push(new);
-            }
+    pub fn is_comparing(&self) -> bool {
+        self.replays.len() > 1
+    }
+
+
+    pub fn stop_comparing(&mut self) {
+        if self.is_comparing() {
+            self.clear_results();
         }
-        if current.1 != *y_max {
-            let new = (current.0, current.1 + 1);
-            if !closed_set.contains(&new) && *self.squares[new.0][new.1].get_square_type() != SquareType::Wall {
-                result.push(new);
+    }
+
+
+    // One state per expanded node, plus the solution at the end.
+    // Expanded nodes are closed, the ones waiting in the open set are open.
+    fn build_states(&self, result: &SearchResult) -> Vec<Board> {
+        let mut board = self.squares.clone();
+        let mut states: Vec<Board> = Vec::new();
+        for step in &result.steps {
+            board[step.current.0][step.current.1].set_square_type(SquareType::Visited);
+            for sq in &step.opened {
+                if board[sq.0][sq.1].square_type == SquareType::Blank {
+                    board[sq.0][sq.1].set_square_type(SquareType::Open);
+                }
             }
+            states.push(board.clone());
         }
-        if current.0 != *x_max {
-            let new = (current.0 + 1, current.1);
-            if !closed_set.contains(&new) && *self.squares[new.0][new.1].get_square_type() != SquareType::Wall {
-                result.push(new);
+        if result.found() {
+            for sq in result.path.iter().filter(|sq| **sq != self.start_square && **sq != self.objective_square) {
+                board[sq.0][sq.1].set_square_type(SquareType::Solution);
             }
+            states.push(board);
         }
-        if current.0 != 0 {
-            let new = (current.0 - 1, current.1);
-            if !closed_set.contains(&new) && *self.squares[new.0][new.1].get_square_type() != SquareType::Wall {
-                result.push(new);
-            }
+        states
+    }
+
+
+    // Why the last search asked for didn't run, if it didn't
+    pub fn get_skipped_search(&self) -> Option<&Outcome> {
+        self.skipped.as_ref()
+    }
+
+
+    // Last search that was run, if any and not comparing
+    pub fn get_result(&self) -> Option<&SearchResult> {
+        self.single_replay().and_then(Replay::result)
+    }
+
+
+    // The replay shown on the board, if there is one and not comparing
+    fn single_replay(&self) -> Option<&Replay> {
+        match self.replays.as_slice() {
+            [replay] => Some(replay),
+            _ => None
         }
-        result
+    }
+
+
+    fn get_replay_length(&self) -> usize {
+        self.replays.iter().map(|replay| replay.length()).max().unwrap_or(0)
+    }
+
+
+    // Replay state being shown (starting at 1) and the number of states
+    pub fn get_replay_position(&self) -> (usize, usize) {
+        (self.current_state + 1, self.get_replay_length())
     }
 
 
     fn clear_results(&mut self) {
-        self.states = Vec::new();
+        self.replays = Vec::new();
+        self.skipped = None;
+        self.current_state = 0;
         for x in 0..self.squares.len() {
             for y in 0..self.squares[0].len() {
                 let sq = &mut self.squares[x][y];
                 let sq_type = sq.get_square_type();
-                if *sq_type == SquareType::Visited || *sq_type == SquareType::Solution {
+                if *sq_type == SquareType::Open || *sq_type == SquareType::Visited || *sq_type == SquareType::Solution {
                     self.squares[x][y].set_square_type(SquareType::Blank);
                 }
             }
@@ -285,103 +1442,176 @@ impl SquareCollection {
     }
 
 
-    fn get_lowest_score(&self, scores: &HashMap<(usize, usize), usize>, open_set:&HashSet<(usize, usize)>, dist: bool) -> (usize, usize) {
-        let mut result = Vec::from([(0, 0)]);
-        let mut minimum = usize::MAX;
-        for tuple in open_set {
-            if *tuple == self.objective_square {
-                return *tuple;
-            }
-            if scores[tuple] < minimum {
-                result = Vec::from([*tuple]);
-                minimum = scores[tuple];
-            } else if scores[tuple] == minimum {
-                result.push(*tuple);
-            }
-        }
-        let mut closest = result.get(0).unwrap();
-        if dist {
-            minimum = self.manhattan_distance(closest);
-            for tuple in &result[1..] {
-                let new_dist = self.manhattan_distance(&tuple);
-                if new_dist < minimum {
-                    minimum = new_dist;
-                    closest = &tuple;
-                }
-            }
-        }
-        *closest
-    }
-
-
     pub fn clear(&mut self) {
+        self.clear_results();
         for x in 0..self.squares.len() {
             for y in 0..self.squares[0].len() {
                 self.squares[x][y].set_square_type(SquareType::Blank);
+                self.squares[x][y].set_cost(1);
+                self.squares[x][y].set_direction(None);
             }
         }
         self.start_square_set = false;
         self.objective_square_set = false;
+        self.pending_portal = None;
+        self.patrols.clear();
+        self.agents.clear();
+        self.pending_agent = None;
     }
 
 
-    pub fn generate_maze(&mut self) {
-        let mut rng = rand::thread_rng();
-        for x in 0..self.squares.len() {
-            for y in 0..self.squares[0].len() {
-                let test = (rng.gen::<f32>() * 5f32) as usize;
-                if test % 5 > 1 {
-                    self.squares[x][y].set_square_type(SquareType::Blank);
-                } else {
-                    self.squares[x][y].set_square_type(SquareType::Wall);
+    // Both ends of the portal become blank squares
+    fn remove_portal(&mut self, id: usize) {
+        for column in self.squares.iter_mut() {
+            for square in column.iter_mut() {
+                if square.square_type == SquareType::Portal(id) {
+                    square.set_square_type(SquareType::Blank);
                 }
             }
         }
-        self.start_square_set = false;
-        self.objective_square_set = false;
+        if self.pending_portal.is_some_and(|pending| self.squares[pending.0][pending.1].square_type == SquareType::Blank) {
+            self.pending_portal = None;
+        }
     }
 
 
-    fn get_current_state(&self) -> Vec<Vec<SquareType>> {
-        let mut result: Vec<Vec<SquareType>> = Vec::new();
-        for x in 0..self.squares.len() {
-            let mut col: Vec<SquareType> = Vec::new();
-            for y in 0..self.squares[0].len() {
-                col.push(self.squares[x][y].square_type.to_owned())
+    // With animate, the generation is loaded as a replay starting at its first step
+    pub fn generate_maze(&mut self, generator: Generator, seed: u64, options: &GeneratorOptions, placement: Placement, animate: bool) {
+        let mut generation = generators::generate(generator, self.squares.len(), self.squares[0].len(), seed, options);
+        generation.grid.set_topology(self.topology);
+        generation.grid.set_wrap(self.wrap);
+        match placement::choose_endpoints(&generation.grid, placement, seed) {
+            Some((start, goal)) => {
+                self.start_square = start;
+                self.objective_square = goal;
+                self.start_square_set = true;
+                self.objective_square_set = true;
+            },
+            None => self.place_endpoints(&generation.grid)
+        }
+        self.set_grid(&generation.grid);
+        if animate {
+            let mut board = generation.initial.clone();
+            let mut states = vec![self.get_grid_state(&board)];
+            for step in &generation.steps {
+                for (pos, cell) in step {
+                    board.set_cell(*pos, *cell);
+                }
+                states.push(self.get_grid_state(&board));
             }
-            result.push(col);
+            self.replays.push(Replay::new(ReplayKind::Generation, states));
+            self.set_replay_state(0);
         }
-        result
     }
 
 
-    pub fn load_next_state(&mut self) {
-        if self.states.len() > 0 {
-            self.current_state += 1;
-            if self.current_state == self.states.len() {
-                self.current_state = 0;
+    pub fn is_generating(&self) -> bool {
+        matches!(self.single_replay(), Some(Replay { kind: ReplayKind::Generation, .. }))
+    }
+
+
+    fn get_grid_state(&self, grid: &Grid) -> Board {
+        let mut board = self.squares.clone();
+        for (x, column) in board.iter_mut().enumerate() {
+            for (y, square) in column.iter_mut().enumerate() {
+                let (square_type, cost, direction) = match grid.get_cell((x, y)) {
+                    Cell::Blank => (SquareType::Blank, 1, None),
+                    Cell::Wall => (SquareType::Wall, 1, None),
+                    Cell::Weighted(cost) => (SquareType::Blank, cost, None),
+                    Cell::OneWay(direction) => (SquareType::Blank, 1, Some(direction))
+                };
+                square.square_type = square_type;
+                square.cost = cost;
+                square.direction = direction;
             }
-            self.load_state();
         }
+        if self.start_square_set {
+            board[self.start_square.0][self.start_square.1].square_type = SquareType::Start;
+        }
+        if self.objective_square_set {
+            board[self.objective_square.0][self.objective_square.1].square_type = SquareType::Objective;
+        }
+        board
     }
 
 
-    pub fn load_previous_state(&mut self) {
-        if self.states.len() > 0 {
-            if self.current_state == 0 {
-                self.current_state = self.states.len() - 1;
-            } else {
-                self.current_state -= 1;
+    // Keeps the start and goal that are set, moving each one to the nearest
+    // square of the largest component of the new grid when it is not there,
+    // so the new map can always be solved between them
+    fn place_endpoints(&mut self, grid: &Grid) {
+        let components = grid.strong_components();
+        let largest = match components.first() {
+            Some(component) => component,
+            None => {
+                self.start_square_set = false;
+                self.objective_square_set = false;
+                return
+            }
+        };
+        let nearest = |sq: (usize, usize), taken: Option<(usize, usize)>| {
+            largest
+                .iter()
+                .filter(|other| Some(**other) != taken)
+                .min_by_key(|other| grid.distance(**other, sq))
+                .cloned()
+        };
+        if self.start_square_set {
+            self.start_square = nearest(self.start_square, None).unwrap();
+        }
+        if self.objective_square_set {
+            let taken = if self.start_square_set { Some(self.start_square) } else { None };
+            match nearest(self.objective_square, taken) {
+                Some(sq) => self.objective_square = sq,
+                None => self.objective_square_set = false
             }
+        }
+    }
+
+
+    // Replaces the whole board with the walls and terrain of the given grid,
+    // keeping the start and goal but not the portals, patrols or agents
+    fn set_grid(&mut self, grid: &Grid) {
+        self.clear_results();
+        let state = self.get_grid_state(grid);
+        SquareCollection::set_board(&mut self.squares, &state);
+        self.pending_portal = None;
+        self.patrols.clear();
+        self.agents.clear();
+        self.pending_agent = None;
+    }
+
+
+    pub fn load_next_state(&mut self) {
+        self.set_replay_state(self.current_state + 1);
+    }
+
+
+    pub fn load_previous_state(&mut self) {
+        self.set_replay_state(self.current_state.saturating_sub(1));
+    }
+
+
+    // Jumps to the given state, stopping at the first and last ones
+    pub fn set_replay_state(&mut self, index: usize) {
+        let length = self.get_replay_length();
+        if length > 0 {
+            self.current_state = index.min(length - 1);
             self.load_state();
         }
     }
 
 
+    // Only a single replay is shown on the board itself
     fn load_state(&mut self) {
-        for x in 0..self.squares.len() {
-            for y in 0..self.squares[0].len() {
-                self.squares[x][y].set_square_type(self.states[self.current_state][x][y].to_owned());
+        if self.replays.len() != 1 { return }
+        SquareCollection::set_board(&mut self.squares, self.replays[0].get_state(self.current_state));
+    }
+
+
+    fn set_board(squares: &mut [Vec<Square>], state: &[Vec<Square>]) {
+        for (column, state_column) in squares.iter_mut().zip(state) {
+            for (square, state_square) in column.iter_mut().zip(state_column) {
+                *square = state_square.clone();
             }
         }
     }